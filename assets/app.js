@@ -1,6 +1,7 @@
 const board = document.getElementById('board');
-const ws = new WebSocket('ws://localhost:3000/ws');
+const ws = new WebSocket(`${location.protocol === 'https:' ? 'wss' : 'ws'}://${location.host}/ws`);
 let currentPlayer = 'black';
+let explaining = false;
 
 function createBoard() {
     for (let i = 0; i < 64; i++) {
@@ -28,6 +29,9 @@ function updateBoard(gameState) {
             piece.className = 'piece white';
             cell.appendChild(piece);
         }
+        if (gameState.stable.includes(index)) {
+            cell.firstChild.classList.add('stable');
+        }
         if (gameState.moves.includes(index)) {
             cell.classList.add('valid-move');
         }
@@ -61,9 +65,110 @@ function xotGame() {
     ws.send(JSON.stringify({ "xot_game": null }));
 }
 
+function stopBot() {
+    ws.send(JSON.stringify({ "stop": null }));
+}
+
+function toggleExplain() {
+    explaining = !explaining;
+    if (explaining) {
+        ws.send(JSON.stringify({ "explain": null }));
+    } else {
+        clearExplanation();
+    }
+}
+
+function squareName(index) {
+    return 'abcdefgh'[index % 8] + (Math.floor(index / 8) + 1);
+}
+
+function highlightFeature(feature) {
+    document.querySelectorAll('.cell').forEach((cell) => {
+        cell.classList.remove('highlight-positive', 'highlight-negative');
+    });
+    if (feature) {
+        const className = feature.weight >= 0 ? 'highlight-positive' : 'highlight-negative';
+        feature.squares.forEach((index) => {
+            document.querySelector(`.cell[data-index="${index}"]`).classList.add(className);
+        });
+    }
+}
+
+function clearExplanation() {
+    document.getElementById('explanation').innerHTML = '';
+    highlightFeature(null);
+}
+
+// Lists the patterns weighing most on the evaluation of the player to move and
+// highlights the strongest, in blue if it favors the player and orange otherwise
+function updateExplanation(explanation) {
+    clearExplanation();
+    const container = document.getElementById('explanation');
+
+    const summary = document.createElement('div');
+    summary.textContent = `Evaluation ${explanation.score} ` +
+        `(${(explanation.total / 128).toFixed(2)} discs for ${currentPlayer})`;
+    container.appendChild(summary);
+
+    const features = explanation.features
+        .filter((feature) => feature.squares.length > 0)
+        .sort((a, b) => Math.abs(b.weight) - Math.abs(a.weight))
+        .slice(0, 5);
+
+    features.forEach((feature) => {
+        const item = document.createElement('div');
+        item.className = 'explanation-feature';
+        item.textContent = `${(feature.weight / 128).toFixed(2).padStart(6)} ` +
+            feature.squares.map(squareName).join(' ');
+        item.addEventListener('mouseenter', () => highlightFeature(feature));
+        item.addEventListener('mouseleave', () => highlightFeature(features[0]));
+        container.appendChild(item);
+    });
+
+    highlightFeature(features[0]);
+}
+
+function updateBots(bots) {
+    document.querySelectorAll('#player-select option').forEach((option) => {
+        option.hidden = option.value !== 'human' && !bots.includes(option.value);
+    });
+    // Explanations use the weights of the edax bot
+    document.getElementById('explain-btn').hidden = !bots.includes('edax');
+}
+
+function updateThinking(thinking) {
+    const search = thinking.is_book ? 'book' :
+        (thinking.is_endgame ? 'endgame' : 'depth ' + thinking.depth) +
+        ` @ ${thinking.selectivity}%`;
+    document.getElementById('thinking').textContent =
+        `${search}: score ${thinking.score}, ${thinking.nodes} nodes, ` +
+        `${thinking.time.toFixed(1)}s, PV ${thinking.pv.join(' ')}`;
+}
+
 ws.onmessage = (event) => {
-    const gameState = JSON.parse(event.data);
-    updateBoard(gameState);
+    const message = JSON.parse(event.data);
+
+    if (message.bots) {
+        updateBots(message.bots);
+        return;
+    }
+
+    if (message.thinking) {
+        updateThinking(message.thinking);
+        return;
+    }
+
+    if (message.explanation) {
+        updateExplanation(message.explanation);
+        return;
+    }
+
+    document.getElementById('thinking').textContent = '';
+    updateBoard(message);
+
+    if (explaining) {
+        ws.send(JSON.stringify({ "explain": null }));
+    }
 };
 
 createBoard();
@@ -77,6 +182,8 @@ document.getElementById('new-game-btn').addEventListener('click', newGame);
 document.getElementById('xot-game-btn').addEventListener('click', xotGame);
 document.getElementById('undo-btn').addEventListener('click', undoMove);
 document.getElementById('redo-btn').addEventListener('click', redoMove);
+document.getElementById('stop-btn').addEventListener('click', stopBot);
+document.getElementById('explain-btn').addEventListener('click', toggleExplain);
 
 document.getElementById('black-player').addEventListener('change', (e) => {
     ws.send(JSON.stringify({
@@ -0,0 +1,47 @@
+use swap::othello::position::{Position, XOT_POSITIONS};
+
+/// Positions from the opening to the endgame: the XOT openings, each continued with a
+/// different number of moves. Moves are picked by a fixed rule, so runs are comparable.
+pub fn positions() -> Vec<Position> {
+    let mut positions = Vec::new();
+
+    for (i, start) in XOT_POSITIONS.iter().take(48).enumerate() {
+        let mut position = *start;
+
+        for ply in 0..i {
+            let moves = position.get_moves();
+            if moves == 0 {
+                position.pass();
+                if !position.has_moves() {
+                    break;
+                }
+                continue;
+            }
+
+            let choice = (ply * 7 + i) % moves.count_ones() as usize;
+            let move_ = (0..choice).fold(moves, |moves, _| moves & (moves - 1));
+            position.do_move(move_.trailing_zeros() as usize);
+        }
+
+        if position.has_moves() {
+            positions.push(position);
+        }
+    }
+
+    positions
+}
+
+/// Returns the valid moves of each position
+pub fn moves(positions: &[Position]) -> Vec<(Position, usize)> {
+    let mut moves = Vec::new();
+
+    for position in positions {
+        let mut remaining = position.get_moves();
+        while remaining != 0 {
+            moves.push((*position, remaining.trailing_zeros() as usize));
+            remaining &= remaining - 1;
+        }
+    }
+
+    moves
+}
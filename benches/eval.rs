@@ -0,0 +1,67 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use swap::bot::edax::eval::Eval;
+use swap::bot::edax::midgame::MidgameSearch;
+use swap::bot::hash_table::HashTable;
+
+mod common;
+
+fn bench_eval(c: &mut Criterion) {
+    let positions = common::positions();
+    let moves = common::moves(&positions);
+
+    let mut group = c.benchmark_group("eval");
+
+    group.throughput(Throughput::Elements(positions.len() as u64));
+    group.bench_function("new", |b| {
+        b.iter(|| {
+            for position in &positions {
+                black_box(Eval::new(black_box(position)));
+            }
+        })
+    });
+
+    // Each move is done and undone on the eval of the position before the move
+    let moves: Vec<_> = moves
+        .into_iter()
+        .map(|(position, move_)| {
+            let mut child = position;
+            let flipped = child.do_move(move_);
+            (Eval::new(&position), move_, flipped)
+        })
+        .collect();
+
+    group.throughput(Throughput::Elements(moves.len() as u64));
+    group.bench_function("update_restore", |b| {
+        b.iter(|| {
+            for (eval, move_, flipped) in &moves {
+                let mut eval = eval.clone();
+                eval.update(black_box(*move_), black_box(*flipped));
+                eval.restore(black_box(*move_), black_box(*flipped));
+                black_box(&eval);
+            }
+        })
+    });
+
+    let hash_table = HashTable::new(10);
+    let searches: Vec<_> = positions
+        .iter()
+        .map(|position| MidgameSearch::new(*position, &hash_table))
+        .collect();
+
+    group.throughput(Throughput::Elements(searches.len() as u64));
+    group.bench_function("heuristic", |b| {
+        b.iter(|| {
+            for search in &searches {
+                black_box(black_box(search).heuristic());
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_eval);
+criterion_main!(benches);
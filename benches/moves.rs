@@ -0,0 +1,95 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+use swap::othello::backend::Backend;
+use swap::othello::do_move::do_move;
+use swap::othello::do_move::edax_bitscan::do_move_edax_bitscan;
+use swap::othello::do_move::edax_slow::do_move_edax_slow;
+use swap::othello::do_move::simple::do_move_simple;
+use swap::othello::get_moves::{get_moves, get_moves_fancy, get_moves_simple};
+use swap::othello::position::Position;
+
+mod common;
+
+fn bench_get_moves(c: &mut Criterion) {
+    let positions = common::positions();
+
+    let mut group = c.benchmark_group("get_moves");
+    group.throughput(Throughput::Elements(positions.len() as u64));
+
+    let mut bench = |name: &str, get_moves: &dyn Fn(u64, u64) -> u64| {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for position in &positions {
+                    black_box(get_moves(
+                        black_box(position.player),
+                        black_box(position.opponent),
+                    ));
+                }
+            })
+        });
+    };
+
+    bench("simple", &get_moves_simple);
+    bench("fancy", &get_moves_fancy);
+    #[cfg(target_arch = "x86_64")]
+    if Backend::Avx2.is_supported() {
+        use swap::othello::get_moves::get_moves_avx2;
+
+        // SAFETY: the CPU supports AVX2, as checked above
+        bench("avx2", &|player, opponent| unsafe {
+            get_moves_avx2(player, opponent)
+        });
+    }
+    bench("default", &get_moves);
+
+    group.finish();
+}
+
+fn bench_do_move(c: &mut Criterion) {
+    let moves = common::moves(&common::positions());
+
+    let mut group = c.benchmark_group("do_move");
+    group.throughput(Throughput::Elements(moves.len() as u64));
+
+    let mut bench = |name: &str, do_move: &dyn Fn(&mut Position, usize) -> u64| {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                for (position, move_) in &moves {
+                    let mut position = *position;
+                    black_box(do_move(&mut position, black_box(*move_)));
+                    black_box(position);
+                }
+            })
+        });
+    };
+
+    bench("simple", &do_move_simple);
+    bench("edax_slow", &do_move_edax_slow);
+    bench("edax_bitscan", &do_move_edax_bitscan);
+    #[cfg(target_arch = "x86_64")]
+    if Backend::Bmi2.is_supported() {
+        use swap::othello::do_move::bmi2::do_move_bmi2;
+
+        // SAFETY: the CPU supports BMI2, as checked above
+        bench("bmi2", &|position, move_| unsafe {
+            do_move_bmi2(position, move_)
+        });
+    }
+    #[cfg(target_arch = "x86_64")]
+    if Backend::Avx2.is_supported() {
+        use swap::othello::do_move::avx2::do_move_avx2;
+
+        // SAFETY: the CPU supports AVX2, as checked above
+        bench("avx2", &|position, move_| unsafe {
+            do_move_avx2(position, move_)
+        });
+    }
+    bench("default", &do_move);
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_get_moves, bench_do_move);
+criterion_main!(benches);
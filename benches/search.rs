@@ -0,0 +1,71 @@
+use std::hint::black_box;
+use std::ops::RangeInclusive;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use swap::bot::edax::endgame::EndgameSearch;
+use swap::bot::edax::midgame::MidgameSearch;
+use swap::bot::hash_table::HashTable;
+use swap::bot::{SearchEvent, SearchLimits};
+use swap::othello::ffo_problems::parse_ffo_problems;
+use swap::othello::position::XOT_POSITIONS;
+
+/// Number of XOT openings searched by the midgame benchmarks
+const MIDGAME_POSITIONS: usize = 8;
+
+/// FFO problems solved by the endgame benchmark, with 14 to 16 empty squares
+const ENDGAME_PROBLEMS: RangeInclusive<usize> = 1..=19;
+
+fn bench_midgame(c: &mut Criterion) {
+    let positions = &XOT_POSITIONS[..MIDGAME_POSITIONS];
+    let mut hash_table = HashTable::new(18);
+
+    let mut group = c.benchmark_group("midgame");
+    group.sample_size(10);
+
+    for depth in [4, 8] {
+        let limits = SearchLimits {
+            depth: Some(depth),
+            ..Default::default()
+        };
+
+        group.bench_function(format!("depth_{}", depth), |b| {
+            b.iter(|| {
+                for position in positions {
+                    // Every search starts from an empty table, as the first move of a game
+                    hash_table.clear();
+                    let mut search = MidgameSearch::new(*position, &hash_table);
+                    black_box(search.search(&limits, &mut |_: SearchEvent| {}));
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_endgame(c: &mut Criterion) {
+    let problems: Vec<_> = parse_ffo_problems()
+        .into_iter()
+        .filter(|problem| ENDGAME_PROBLEMS.contains(&problem.line_number))
+        .collect();
+    let mut hash_table = HashTable::new(20);
+
+    let mut group = c.benchmark_group("endgame");
+    group.sample_size(10);
+
+    group.bench_function("ffo_1_19", |b| {
+        b.iter(|| {
+            for problem in &problems {
+                hash_table.clear();
+                let mut search = EndgameSearch::new(problem.position, &hash_table);
+                black_box(search.search(&SearchLimits::default(), &mut |_: SearchEvent| {}));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_midgame, bench_endgame);
+criterion_main!(benches);
@@ -0,0 +1,48 @@
+// With the `embedded-data` feature, compresses the data files into the output directory,
+// from where `src/embedded.rs` includes them in the binary.
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "embedded-data")]
+    embedded::compress_data_files();
+}
+
+#[cfg(feature = "embedded-data")]
+mod embedded {
+    use std::env;
+    use std::fs::File;
+    use std::io::{self, BufReader, BufWriter};
+    use std::path::Path;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    /// Files compiled into the binary, relative to the package
+    const DATA_FILES: [&str; 3] = ["eval.dat", "assets/xot.json", "assets/ffo_problems.txt"];
+
+    pub fn compress_data_files() {
+        let out_dir = env::var("OUT_DIR").unwrap();
+
+        for file in DATA_FILES {
+            println!("cargo:rerun-if-changed={}", file);
+
+            let name = file.rsplit('/').next().unwrap();
+            let target = Path::new(&out_dir).join(format!("{}.gz", name));
+
+            if let Err(error) = compress(Path::new(file), &target) {
+                panic!("Cannot embed {}: {}", file, error);
+            }
+        }
+    }
+
+    fn compress(source: &Path, target: &Path) -> io::Result<()> {
+        let mut reader = BufReader::new(File::open(source)?);
+        let mut encoder =
+            GzEncoder::new(BufWriter::new(File::create(target)?), Compression::best());
+
+        io::copy(&mut reader, &mut encoder)?;
+        encoder.finish()?;
+        Ok(())
+    }
+}
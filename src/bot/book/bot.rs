@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use crate::bot::{Bot, RootMoveScore, SearchEvent, SearchLimits, SearchObserver, SearchResult};
+use crate::othello::position::Position;
+
+use super::Book;
+
+/// Plays from a book while the position is in it, otherwise lets another bot search.
+pub struct BookBot {
+    book: Arc<Book>,
+    bot: Box<dyn Bot>,
+
+    /// Maximum number of discs a book move may score less than the best book move
+    randomness: i32,
+}
+
+impl BookBot {
+    pub fn new(book: Arc<Book>, bot: Box<dyn Bot>, randomness: i32) -> Self {
+        Self {
+            book,
+            bot,
+            randomness,
+        }
+    }
+}
+
+impl Bot for BookBot {
+    fn search(
+        &mut self,
+        position: &Position,
+        limits: &SearchLimits,
+        observer: &mut dyn SearchObserver,
+    ) -> SearchResult {
+        let Some(book_move) =
+            self.book
+                .choose_move(position, self.randomness, &mut rand::thread_rng())
+        else {
+            return self.bot.search(position, limits, observer);
+        };
+
+        let mut result = SearchResult::unsearched(book_move.move_);
+        result.score = book_move.score as isize;
+        result.is_book = true;
+        result.root_moves = self
+            .book
+            .get_moves(position)
+            .into_iter()
+            .map(|book_move| RootMoveScore {
+                move_: book_move.move_,
+                score: book_move.score as isize,
+                is_upper_bound: false,
+            })
+            .collect();
+
+        observer.notify(SearchEvent::DepthCompleted(&result));
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::book::{BookEntry, BookMove};
+    use crate::bot::random::RandomBot;
+    use crate::othello::squares::*;
+
+    #[test]
+    fn test_book_bot() {
+        let position = Position::new();
+        let mut book = Book::new();
+        book.insert(
+            position,
+            BookEntry {
+                links: vec![
+                    BookMove {
+                        move_: D3,
+                        score: 0,
+                    },
+                    BookMove {
+                        move_: C4,
+                        score: -4,
+                    },
+                ],
+                ..Default::default()
+            },
+        );
+
+        let mut bot = BookBot::new(Arc::new(book), Box::new(RandomBot), 0);
+
+        let mut events = 0;
+        let result = bot.search(
+            &position,
+            &SearchLimits::default(),
+            &mut |_: SearchEvent| events += 1,
+        );
+
+        assert_eq!(events, 1);
+        assert_eq!(result.best_move, D3);
+        assert!(result.is_book);
+        assert_eq!(result.root_moves.len(), 2);
+
+        // Positions outside of the book are searched by the other bot
+        let child = position.do_move_cloned(F5);
+        let result = bot.search(&child, &SearchLimits::default(), &mut |_: SearchEvent| {});
+        assert!(!result.is_book);
+        assert!(child.is_valid_move(result.best_move));
+    }
+}
@@ -0,0 +1,527 @@
+use std::collections::BinaryHeap;
+use std::io;
+
+use crate::bot::edax::midgame::MidgameSearch;
+use crate::bot::hash_table::{HashTable, DEFAULT_HASH_TABLE_BITS};
+use crate::bot::{SearchEvent, SearchLimits};
+use crate::othello::position::Position;
+use crate::othello::squares::parse_moves;
+
+use super::{Book, BookMove};
+
+/// Settings for growing a book
+#[derive(Clone, Copy, Debug)]
+pub struct BuildOptions {
+    /// Depth of the midgame search that scores moves leaving the book
+    pub depth: u32,
+
+    /// Positions with fewer empty squares are not added
+    pub min_empties: u32,
+
+    /// Leaves scoring more discs than this below the best move of their position
+    /// are not expanded
+    pub max_error: i32,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        Self {
+            depth: 10,
+            min_empties: 30,
+            max_error: 4,
+        }
+    }
+}
+
+/// Grows a book by adding positions and expanding leaves, as Edax's book commands do.
+///
+/// Positions are stored in their canonical form. Each has links to the moves that
+/// lead to other book positions, and a leaf for the best move that leaves the book,
+/// as scored by a midgame search. Book scores are the negamax of links and leaves.
+pub struct BookBuilder {
+    book: Book,
+    options: BuildOptions,
+    hash_table: HashTable,
+
+    /// Leaves to expand by priority, see `leaf_priority`. Positions are pushed again
+    /// when they change, so outdated priorities are skipped when popped.
+    leaves: BinaryHeap<LeafPriority>,
+}
+
+/// Number of empty squares, leaf score and position, highest expanded first
+type LeafPriority = (u32, i32, u64, u64);
+
+impl BookBuilder {
+    pub fn new(book: Book, options: BuildOptions) -> Self {
+        Self {
+            book,
+            options,
+            hash_table: HashTable::new(DEFAULT_HASH_TABLE_BITS),
+            leaves: BinaryHeap::new(),
+        }
+    }
+
+    pub fn book(&self) -> &Book {
+        &self.book
+    }
+
+    pub fn into_book(self) -> Book {
+        self.book
+    }
+
+    /// Returns the score of `position` for the player to move
+    fn search(&mut self, position: &Position) -> i32 {
+        if !position.has_moves() {
+            let mut passed = *position;
+            passed.pass();
+
+            if !passed.has_moves() {
+                return position.final_score() as i32;
+            }
+            return -self.search(&passed);
+        }
+
+        let limits = SearchLimits {
+            depth: Some(self.options.depth),
+            ..Default::default()
+        };
+
+        self.hash_table.new_search();
+        MidgameSearch::new(*position, &self.hash_table)
+            .search(&limits, &mut |_: SearchEvent| {})
+            .score as i32
+    }
+
+    /// Returns the position that the book stores for the position after `move_`,
+    /// and whether its player to move is the opponent of the player making the move.
+    /// Returns `None` if the game is over after the move.
+    fn child(position: &Position, move_: usize) -> Option<(Position, bool)> {
+        let mut child = position.do_move_cloned(move_);
+
+        if child.has_moves() {
+            return Some((child.canonical().0, true));
+        }
+
+        child.pass();
+        if child.has_moves() {
+            return Some((child.canonical().0, false));
+        }
+
+        None
+    }
+
+    /// Returns the book score of `move_` for the player making it,
+    /// if the position after the move is in the book
+    fn link_score(&self, position: &Position, move_: usize) -> Option<i32> {
+        let (child, is_opponent) = Self::child(position, move_)?;
+        let score = self.book.get(&child)?.score;
+
+        Some(if is_opponent { -score } else { score })
+    }
+
+    /// Recomputes the links, leaf and score of the stored `position`. The leaf is only
+    /// searched again if it is missing or its move now leads into the book.
+    fn update(&mut self, position: &Position) {
+        let mut entry = self.book.get(position).cloned().unwrap_or_default();
+
+        let mut links = vec![];
+        let mut other_moves = vec![];
+
+        let mut moves = position.get_moves();
+        while moves != 0 {
+            let move_ = moves.trailing_zeros() as usize;
+            moves &= moves - 1;
+
+            match self.link_score(position, move_) {
+                Some(score) => links.push(BookMove { move_, score }),
+                None => other_moves.push(move_),
+            }
+        }
+
+        let leaf_is_valid = entry
+            .leaf
+            .is_some_and(|leaf| other_moves.contains(&leaf.move_));
+
+        if !leaf_is_valid {
+            entry.leaf = other_moves
+                .into_iter()
+                .map(|move_| BookMove {
+                    move_,
+                    score: -self.search(&position.do_move_cloned(move_)),
+                })
+                .max_by_key(|leaf| leaf.score);
+        }
+
+        entry.links = links;
+        if let Some(score) = entry.moves().map(|m| m.score).max() {
+            entry.score = score;
+        }
+        entry.lower = -64;
+        entry.upper = 64;
+        entry.level = self.options.depth as u8;
+
+        self.book.insert(*position, entry);
+        self.leaves.extend(self.leaf_priority(position));
+    }
+
+    /// Adds `position` to the book if it has moves and enough empty squares,
+    /// and links it from `parent`. Returns true if the position was new.
+    fn add(&mut self, position: &Position, parent: Option<&Position>) -> bool {
+        if !position.has_moves() || position.count_empty() < self.options.min_empties {
+            return false;
+        }
+
+        let position = position.canonical().0;
+        let is_new = self.book.get(&position).is_none();
+
+        if is_new {
+            self.update(&position);
+        }
+
+        if let Some(parent) = parent {
+            let parent = parent.canonical().0;
+            if self.book.get(&parent).is_some() {
+                self.update(&parent);
+            }
+        }
+
+        is_new
+    }
+
+    /// Adds a position from which book lines start, such as the start of the game or
+    /// a XOT opening. Returns true if the position was new.
+    pub fn add_root(&mut self, position: &Position) -> bool {
+        self.add(position, None)
+    }
+
+    /// Adds the positions of a game played from `start`, passes are implied.
+    /// Returns the number of new positions.
+    pub fn add_game(&mut self, start: &Position, moves: &[usize]) -> Result<usize, String> {
+        let mut position = *start;
+        let mut parent = None;
+        let mut added = 0;
+
+        for (ply, &move_) in moves.iter().enumerate() {
+            if !position.has_moves() {
+                position.pass();
+            }
+
+            if !position.is_valid_move(move_) {
+                return Err(format!("Invalid move at ply {}", ply + 1));
+            }
+
+            if position.count_empty() < self.options.min_empties {
+                break;
+            }
+
+            if self.add(&position, parent.as_ref()) {
+                added += 1;
+            }
+
+            parent = Some(position);
+            position.do_move(move_);
+        }
+
+        if self.add(&position, parent.as_ref()) {
+            added += 1;
+        }
+
+        Ok(added)
+    }
+
+    /// Adds the games of a text with one transcript per line, such as "f5d6c3d3c4",
+    /// played from the start of the game. Returns the number of new positions.
+    pub fn import_games(&mut self, text: &str) -> io::Result<usize> {
+        let mut added = 0;
+
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = |message: String| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Line {}: {}", line_number + 1, message),
+                )
+            };
+
+            let moves = parse_moves(line).ok_or_else(|| error("Invalid transcript".into()))?;
+            added += self.add_game(&Position::new(), &moves).map_err(error)?;
+        }
+
+        Ok(added)
+    }
+
+    /// Returns the priority of expanding the leaf of the stored `position`, or `None` if
+    /// it has no leaf to expand. Leaves closest to the start of the game come first,
+    /// among those within `max_error` of their position's score.
+    fn leaf_priority(&self, position: &Position) -> Option<LeafPriority> {
+        let entry = self.book.get(position)?;
+        let leaf = entry.leaf?;
+
+        if leaf.score < entry.score - self.options.max_error
+            || position.count_empty() <= self.options.min_empties
+        {
+            return None;
+        }
+
+        Self::child(position, leaf.move_)?;
+        Some((
+            position.count_empty(),
+            leaf.score,
+            position.player,
+            position.opponent,
+        ))
+    }
+
+    /// Returns the book position whose leaf is expanded next, see `leaf_priority`
+    fn next_leaf(&mut self) -> Option<(Position, BookMove)> {
+        while let Some(priority) = self.leaves.pop() {
+            let (_, _, player, opponent) = priority;
+            let position = Position::new_from_bitboards(player, opponent);
+
+            if self.leaf_priority(&position) == Some(priority) {
+                return Some((position, self.book.get(&position)?.leaf?));
+            }
+        }
+
+        None
+    }
+
+    /// Adds the positions after up to `n_leaves` leaf moves to the book and updates the
+    /// scores. Returns the number of positions added, which is lower if no leaves are left.
+    pub fn expand(&mut self, n_leaves: usize) -> usize {
+        // Scores may have changed since the last expansion, so all leaves are queued again
+        let leaves = self
+            .book
+            .entries
+            .keys()
+            .filter_map(|&(player, opponent)| {
+                self.leaf_priority(&Position::new_from_bitboards(player, opponent))
+            })
+            .collect();
+        self.leaves = leaves;
+
+        let mut added = 0;
+
+        while added < n_leaves {
+            let Some((position, leaf)) = self.next_leaf() else {
+                break;
+            };
+
+            let mut child = position.do_move_cloned(leaf.move_);
+            if !child.has_moves() {
+                child.pass();
+            }
+
+            if self.add(&child, Some(&position)) {
+                added += 1;
+            } else if self.book.get(&child.canonical().0).is_none() {
+                // The child can't be stored, don't try this leaf again. If it was
+                // already stored, `add` linked it and searched a new leaf instead.
+                self.book
+                    .entries
+                    .get_mut(&(position.player, position.opponent))
+                    .unwrap()
+                    .leaf = None;
+            }
+        }
+
+        self.negamax();
+        added
+    }
+
+    /// Recomputes the scores of all positions from the leaves towards the roots
+    pub fn negamax(&mut self) {
+        let mut positions: Vec<_> = self
+            .book
+            .entries
+            .keys()
+            .map(|&(player, opponent)| Position::new_from_bitboards(player, opponent))
+            .collect();
+
+        // Children have fewer empty squares, so they are updated before their parents
+        positions.sort_by_key(|position| position.count_empty());
+
+        for position in positions {
+            let mut entry = self.book.get(&position).cloned().unwrap();
+
+            // Links of imported books may lead to positions that are not stored, and
+            // some of their entries have no moves at all, those keep their scores
+            for link in &mut entry.links {
+                if let Some(score) = self.link_score(&position, link.move_) {
+                    link.score = score;
+                }
+            }
+            if let Some(score) = entry.moves().map(|m| m.score).max() {
+                entry.score = score;
+            }
+
+            self.book.insert(position, entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::book::BookEntry;
+    use crate::othello::squares::*;
+
+    fn options() -> BuildOptions {
+        BuildOptions {
+            depth: 2,
+            min_empties: 54,
+            max_error: 64,
+        }
+    }
+
+    /// Checks that every position is stored canonically with consistent links and scores
+    fn assert_consistent(builder: &BookBuilder) {
+        let book = builder.book();
+
+        for (&(player, opponent), entry) in &book.entries {
+            let position = Position::new_from_bitboards(player, opponent);
+            assert_eq!(position.canonical().0, position);
+
+            for link in &entry.links {
+                assert_eq!(builder.link_score(&position, link.move_), Some(link.score));
+            }
+
+            if let Some(leaf) = entry.leaf {
+                assert!(position.is_valid_move(leaf.move_));
+                assert!(builder.link_score(&position, leaf.move_).is_none());
+            }
+
+            let best = entry.moves().map(|m| m.score).max().unwrap();
+            assert_eq!(entry.score, best);
+        }
+    }
+
+    #[test]
+    fn test_add_root() {
+        let mut builder = BookBuilder::new(Book::new(), options());
+
+        assert!(builder.add_root(&Position::new()));
+        assert!(!builder.add_root(&Position::new()));
+
+        let entry = builder.book().get(&Position::new().canonical().0).unwrap();
+        assert!(entry.links.is_empty());
+        assert!(entry.leaf.is_some());
+        assert_eq!(entry.level, 2);
+        assert_consistent(&builder);
+    }
+
+    #[test]
+    fn test_add_game() {
+        let mut builder = BookBuilder::new(Book::new(), options());
+
+        let added = builder
+            .add_game(&Position::new(), &[F5, D6, C3, D3, C4])
+            .unwrap();
+        assert_eq!(added, 6);
+
+        // Symmetric games share positions
+        let added = builder
+            .add_game(&Position::new(), &[E6, F4, C3, C4, D3])
+            .unwrap();
+        assert_eq!(added, 0);
+
+        let added = builder.add_game(&Position::new(), &[F5, D6, C5]).unwrap();
+        assert_eq!(added, 1);
+
+        // All first moves lead to the same stored position
+        let start = builder.book().get(&Position::new().canonical().0).unwrap();
+        assert_eq!(start.links.len(), 4);
+        assert!(start.leaf.is_none());
+        assert_consistent(&builder);
+
+        assert!(builder.add_game(&Position::new(), &[F5, F5]).is_err());
+    }
+
+    #[test]
+    fn test_import_games() {
+        let mut builder = BookBuilder::new(Book::new(), options());
+
+        let text = "# Opening lines\nf5d6c3\n\nf5f6\n";
+        assert_eq!(builder.import_games(text).unwrap(), 5);
+
+        let error = builder.import_games("f5d6\nf5x9\n").err().unwrap();
+        assert!(error.to_string().starts_with("Line 2"));
+    }
+
+    #[test]
+    fn test_expand() {
+        let mut builder = BookBuilder::new(Book::new(), options());
+        builder.add_root(&Position::new());
+
+        assert_eq!(builder.expand(10), 10);
+        assert_eq!(builder.book().len(), 11);
+        assert_consistent(&builder);
+
+        // Stops when all leaves reached the minimum number of empties
+        let mut builder = BookBuilder::new(
+            Book::new(),
+            BuildOptions {
+                min_empties: 58,
+                ..options()
+            },
+        );
+        builder.add_root(&Position::new());
+        assert_eq!(builder.expand(100), 4);
+        assert_consistent(&builder);
+
+        // The bot plays book lines
+        let book = builder.into_book();
+        let moves = book.get_moves(&Position::new());
+        assert_eq!(moves.len(), 4);
+    }
+
+    #[test]
+    fn test_expand_transposition() {
+        let mut builder = BookBuilder::new(Book::new(), options());
+        let position = Position::new().do_move_cloned(F5);
+        builder.add_root(&position);
+
+        // Store the position after the leaf move without linking it, as a transposition
+        // from another line would
+        let leaf = builder.book().get(&position.canonical().0).unwrap().leaf;
+        let leaf_move = position.canonical().0.do_move_cloned(leaf.unwrap().move_);
+        builder.add_root(&leaf_move);
+
+        // The leaf is linked and the next one searched and expanded, not dropped, which
+        // leaves the third move of the position as its leaf
+        assert_eq!(builder.expand(1), 1);
+        let entry = builder.book().get(&position.canonical().0).unwrap();
+        assert_eq!(entry.links.len(), 2);
+        assert!(entry.leaf.is_some());
+        assert_consistent(&builder);
+    }
+
+    #[test]
+    fn test_negamax_imported_entries() {
+        let mut book = Book::new();
+        let position = Position::new();
+        let entry = BookEntry {
+            score: 2,
+            links: vec![BookMove {
+                move_: F5,
+                score: 2,
+            }],
+            ..Default::default()
+        };
+        book.insert(position, entry.clone());
+        book.insert(
+            position.do_move_cloned(F5).do_move_cloned(D6),
+            BookEntry::default(),
+        );
+
+        // Links to missing positions and entries without moves keep their scores
+        let mut builder = BookBuilder::new(book, options());
+        builder.negamax();
+        assert_eq!(builder.book().get(&position), Some(&entry));
+        assert_eq!(builder.expand(10), 0);
+    }
+}
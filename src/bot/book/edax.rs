@@ -0,0 +1,221 @@
+// This reads the opening book format of Edax 4,
+// see https://github.com/abulmo/edax-reversi/blob/master/src/book.c
+
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+use crate::othello::position::Position;
+
+use super::{Book, BookEntry, BookMove};
+
+const EDAX: u32 = 0x45444158; // "EDAX" as stored by Edax on little-endian machines
+const BOOK: u32 = 0x424f4f4b; // "BOOK" as stored by Edax on little-endian machines
+
+/// Major version of Edax that wrote the book
+const VERSION: u8 = 4;
+
+/// Value of the leaf move when a position has no leaf
+const NO_MOVE: u8 = 65;
+
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(read_bytes(reader)?))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    Ok(u64::from_le_bytes(read_bytes(reader)?))
+}
+
+fn read_i16(reader: &mut impl Read) -> io::Result<i32> {
+    Ok(i16::from_le_bytes(read_bytes(reader)?) as i32)
+}
+
+/// Reads a move and its score, which Edax stores as two bytes
+fn read_link(reader: &mut impl Read) -> io::Result<BookMove> {
+    let [score, move_] = read_bytes(reader)?;
+
+    Ok(BookMove {
+        move_: move_ as usize,
+        score: score as i8 as i32,
+    })
+}
+
+impl Book {
+    /// Loads a book in the binary format of Edax
+    pub fn load_edax(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Self::read_edax(&mut BufReader::new(file))
+    }
+
+    pub fn read_edax(reader: &mut impl Read) -> io::Result<Self> {
+        let edax_header = read_u32(reader)?;
+        let book_header = read_u32(reader)?;
+
+        if edax_header != EDAX || book_header != BOOK {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not an Edax book file",
+            ));
+        }
+
+        let [version, _release] = read_bytes(reader)?;
+        if version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unsupported Edax book version {}", version),
+            ));
+        }
+
+        // Date of the last change (8 bytes) and the options used to build the book:
+        // level, number of empties, midgame error, endcut error and verbosity.
+        read_bytes::<28>(reader)?;
+
+        let n_positions = read_u32(reader)?;
+        let mut book = Book::new();
+
+        for _ in 0..n_positions {
+            let player = read_u64(reader)?;
+            let opponent = read_u64(reader)?;
+
+            let mut entry = BookEntry {
+                wins: read_u32(reader)?,
+                draws: read_u32(reader)?,
+                losses: read_u32(reader)?,
+                lines: read_u32(reader)?,
+                score: read_i16(reader)?,
+                lower: read_i16(reader)?,
+                upper: read_i16(reader)?,
+                ..Default::default()
+            };
+
+            let [n_links, level] = read_bytes(reader)?;
+            entry.level = level;
+
+            for _ in 0..n_links {
+                entry.links.push(read_link(reader)?);
+            }
+
+            let leaf = read_link(reader)?;
+            if leaf.move_ != NO_MOVE as usize {
+                entry.leaf = Some(leaf);
+            }
+
+            book.insert(Position::new_from_bitboards(player, opponent), entry);
+        }
+
+        Ok(book)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::othello::squares::*;
+
+    /// Returns the bytes of an Edax book with the given positions
+    fn edax_book(positions: &[(Position, BookEntry)]) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend(EDAX.to_le_bytes());
+        bytes.extend(BOOK.to_le_bytes());
+        bytes.extend([VERSION, 4]);
+        bytes.extend([0; 28]);
+        bytes.extend((positions.len() as u32).to_le_bytes());
+
+        for (position, entry) in positions {
+            bytes.extend(position.player.to_le_bytes());
+            bytes.extend(position.opponent.to_le_bytes());
+            for count in [entry.wins, entry.draws, entry.losses, entry.lines] {
+                bytes.extend(count.to_le_bytes());
+            }
+            for score in [entry.score, entry.lower, entry.upper] {
+                bytes.extend((score as i16).to_le_bytes());
+            }
+            bytes.extend([entry.links.len() as u8, entry.level]);
+
+            let leaf = entry.leaf.unwrap_or(BookMove {
+                move_: NO_MOVE as usize,
+                score: 0,
+            });
+            for link in entry.links.iter().chain([&leaf]) {
+                bytes.extend([link.score as i8 as u8, link.move_ as u8]);
+            }
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn test_read_edax() {
+        let position = Position::new();
+        let entry = BookEntry {
+            score: 0,
+            lower: -64,
+            upper: 64,
+            wins: 3,
+            draws: 1,
+            losses: 2,
+            lines: 6,
+            level: 21,
+            links: vec![
+                BookMove {
+                    move_: D3,
+                    score: 0,
+                },
+                BookMove {
+                    move_: C4,
+                    score: -1,
+                },
+            ],
+            leaf: Some(BookMove {
+                move_: F5,
+                score: -2,
+            }),
+        };
+
+        let child = position.do_move_cloned(D3);
+        let child_entry = BookEntry {
+            links: vec![BookMove {
+                move_: C3,
+                score: 0,
+            }],
+            ..Default::default()
+        };
+
+        let bytes = edax_book(&[(position, entry.clone()), (child, child_entry.clone())]);
+        let book = Book::read_edax(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(book.len(), 2);
+        assert_eq!(book.get(&position), Some(&entry));
+        assert_eq!(book.get(&child), Some(&child_entry));
+    }
+
+    #[test]
+    fn test_read_edax_errors() {
+        let mut bytes = edax_book(&[(Position::new(), BookEntry::default())]);
+
+        // Truncated file
+        let truncated = &bytes[..bytes.len() - 1];
+        assert!(Book::read_edax(&mut &truncated[..]).is_err());
+
+        // Wrong version
+        bytes[8] = 3;
+        let error = Book::read_edax(&mut bytes.as_slice()).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // Not a book
+        let error = Book::read_edax(&mut &b"EDAXEVAL"[..]).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_load_edax_missing() {
+        let error = Book::load_edax("missing.dat").err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,250 @@
+// Book file format of swap. All numbers are little-endian.
+//
+// Header: "SWAPBOOK", format version (u8), number of positions (u32)
+// Per position:
+// - player and opponent bitboards (u64 each)
+// - score (i8), search depth (u8) and number of links (u8)
+// - each link as move (u8) and score (i8)
+// - leaf move (u8, `NO_MOVE` if there is no leaf) and score (i8)
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::othello::position::Position;
+
+use super::{Book, BookEntry, BookMove};
+
+const MAGIC: &[u8; 8] = b"SWAPBOOK";
+
+const VERSION: u8 = 1;
+
+/// Value of the leaf move when a position has no leaf
+const NO_MOVE: u8 = 65;
+
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_move(reader: &mut impl Read) -> io::Result<BookMove> {
+    let [move_, score] = read_bytes(reader)?;
+
+    Ok(BookMove {
+        move_: move_ as usize,
+        score: score as i8 as i32,
+    })
+}
+
+fn write_move(writer: &mut impl Write, book_move: &BookMove) -> io::Result<()> {
+    writer.write_all(&[book_move.move_ as u8, book_move.score as i8 as u8])
+}
+
+impl Book {
+    /// Loads a book written by `save` or by Edax
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let magic: [u8; 8] = read_bytes(&mut reader)?;
+        if &magic == MAGIC {
+            return Self::read_body(&mut reader);
+        }
+
+        Self::read_edax(&mut (&magic[..]).chain(reader))
+    }
+
+    /// Writes the book to `path`. The file is replaced only after writing succeeded,
+    /// so an interrupted save keeps the previous book.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let temp_path = path.with_extension("tmp");
+
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        drop(writer);
+
+        fs::rename(temp_path, path)
+    }
+
+    pub fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let magic: [u8; 8] = read_bytes(reader)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not a swap book file",
+            ));
+        }
+
+        Self::read_body(reader)
+    }
+
+    /// Reads what follows the magic bytes
+    fn read_body(reader: &mut impl Read) -> io::Result<Self> {
+        let [version] = read_bytes(reader)?;
+        if version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unsupported book version {}", version),
+            ));
+        }
+
+        let n_positions = u32::from_le_bytes(read_bytes(reader)?);
+        let mut book = Book::new();
+
+        for _ in 0..n_positions {
+            let player = u64::from_le_bytes(read_bytes(reader)?);
+            let opponent = u64::from_le_bytes(read_bytes(reader)?);
+            let [score, level, n_links] = read_bytes(reader)?;
+
+            let mut entry = BookEntry {
+                score: score as i8 as i32,
+                lower: -64,
+                upper: 64,
+                level,
+                ..Default::default()
+            };
+
+            for _ in 0..n_links {
+                entry.links.push(read_move(reader)?);
+            }
+
+            let leaf = read_move(reader)?;
+            if leaf.move_ != NO_MOVE as usize {
+                entry.leaf = Some(leaf);
+            }
+
+            book.insert(Position::new_from_bitboards(player, opponent), entry);
+        }
+
+        Ok(book)
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        writer.write_all(&(self.len() as u32).to_le_bytes())?;
+
+        // Sorted, so that the same book is always written the same way
+        let mut keys: Vec<_> = self.entries.keys().collect();
+        keys.sort();
+
+        for key in keys {
+            let entry = &self.entries[key];
+
+            writer.write_all(&key.0.to_le_bytes())?;
+            writer.write_all(&key.1.to_le_bytes())?;
+            writer.write_all(&[
+                entry.score as i8 as u8,
+                entry.level,
+                entry.links.len() as u8,
+            ])?;
+
+            for link in &entry.links {
+                write_move(writer, link)?;
+            }
+
+            let no_leaf = BookMove {
+                move_: NO_MOVE as usize,
+                score: 0,
+            };
+            write_move(writer, entry.leaf.as_ref().unwrap_or(&no_leaf))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::othello::squares::*;
+
+    fn test_book() -> Book {
+        let mut book = Book::new();
+        let position = Position::new();
+
+        book.insert(
+            position,
+            BookEntry {
+                score: 1,
+                lower: -64,
+                upper: 64,
+                level: 10,
+                links: vec![BookMove {
+                    move_: D3,
+                    score: -3,
+                }],
+                leaf: Some(BookMove {
+                    move_: C4,
+                    score: 1,
+                }),
+                ..Default::default()
+            },
+        );
+        book.insert(
+            position.do_move_cloned(D3),
+            BookEntry {
+                score: 3,
+                lower: -64,
+                upper: 64,
+                level: 10,
+                ..Default::default()
+            },
+        );
+
+        book
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let book = test_book();
+
+        let mut bytes = vec![];
+        book.write(&mut bytes).unwrap();
+
+        // 13 bytes header, 21 bytes per position plus 2 per link
+        assert_eq!(bytes.len(), 13 + 2 * 21 + 2);
+
+        let read = Book::read(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read.entries, book.entries);
+
+        // Writing is deterministic
+        let mut rewritten = vec![];
+        read.write(&mut rewritten).unwrap();
+        assert_eq!(rewritten, bytes);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let mut bytes = vec![];
+        test_book().write(&mut bytes).unwrap();
+
+        let truncated = &bytes[..bytes.len() - 1];
+        assert!(Book::read(&mut &truncated[..]).is_err());
+
+        bytes[8] = VERSION + 1;
+        let error = Book::read(&mut bytes.as_slice()).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let error = Book::read(&mut &b"EDAXBOOK"[..]).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = std::env::temp_dir().join(format!("swap-book-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.book");
+
+        let book = test_book();
+        book.save(&path).unwrap();
+        assert!(!path.with_extension("tmp").exists());
+
+        let loaded = Book::load(&path).unwrap();
+        assert_eq!(loaded.entries, book.entries);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
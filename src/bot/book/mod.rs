@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::othello::position::Position;
+use crate::othello::symmetry::Symmetry;
+
+pub mod bot;
+pub mod builder;
+pub mod edax;
+pub mod file;
+
+/// Book file loaded when no other one is set with `set_book_path` or `BOOK_PATH_VAR`.
+/// Unlike the evaluation weights, books are not part of the `embedded-data` feature:
+/// they are optional, not in the repository, and grow as `swap book` expands them.
+pub const DEFAULT_BOOK_PATH: &str = "book.dat";
+
+/// Environment variable with the path of the book file
+pub const BOOK_PATH_VAR: &str = "SWAP_BOOK";
+
+static BOOK_PATH: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+static LOADED_BOOK: OnceLock<Result<Option<Arc<Book>>, String>> = OnceLock::new();
+
+/// Sets the book file bots play from, instead of the one of `BOOK_PATH_VAR`.
+/// Fails once the book is loaded or the path was already set.
+pub fn set_book_path(path: PathBuf) -> Result<(), String> {
+    BOOK_PATH
+        .set(Some(path))
+        .map_err(|_| "The book file is already chosen".to_string())
+}
+
+/// Loads the book on the first call and returns the same result on later ones.
+/// A missing `DEFAULT_BOOK_PATH` means there is no book, while a chosen file must exist.
+pub fn load_book() -> Result<Option<Arc<Book>>, String> {
+    LOADED_BOOK
+        .get_or_init(|| {
+            let chosen = BOOK_PATH
+                .get_or_init(|| std::env::var_os(BOOK_PATH_VAR).map(PathBuf::from))
+                .as_deref();
+            let path = chosen.unwrap_or(Path::new(DEFAULT_BOOK_PATH));
+
+            match Book::load(path) {
+                Ok(book) => Ok(Some(Arc::new(book))),
+                Err(error) if error.kind() == io::ErrorKind::NotFound && chosen.is_none() => {
+                    Ok(None)
+                }
+                Err(error) => Err(format!(
+                    "Cannot load the book from {}: {}",
+                    path.display(),
+                    error
+                )),
+            }
+        })
+        .clone()
+}
+
+/// Move of a book position with its score for the player making it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BookMove {
+    pub move_: usize,
+    pub score: i32,
+}
+
+/// Book data of a position. Moves refer to the squares of the position as it is stored.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct BookEntry {
+    /// Best score for the player to move, negamaxed from the links and leaf
+    pub score: i32,
+
+    /// Bounds of the score, as far as they are proven
+    pub lower: i32,
+    pub upper: i32,
+
+    /// Results of the games played through this position
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+
+    /// Number of lines of play through this position
+    pub lines: u32,
+
+    /// Search level used to evaluate the moves
+    pub level: u8,
+
+    /// Moves leading to positions that are in the book as well
+    pub links: Vec<BookMove>,
+
+    /// Best move that leads out of the book, if any
+    pub leaf: Option<BookMove>,
+}
+
+impl BookEntry {
+    /// Returns the links followed by the leaf
+    pub fn moves(&self) -> impl Iterator<Item = BookMove> + '_ {
+        self.links.iter().copied().chain(self.leaf)
+    }
+}
+
+/// Positions with known good moves, looked up under all symmetries of the board.
+#[derive(Default)]
+pub struct Book {
+    entries: HashMap<(u64, u64), BookEntry>,
+}
+
+impl Book {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Stores `entry` for `position`, as is
+    pub fn insert(&mut self, position: Position, entry: BookEntry) {
+        self.entries
+            .insert((position.player, position.opponent), entry);
+    }
+
+    /// Returns the entry of `position` as it is stored, without trying other symmetries
+    pub fn get(&self, position: &Position) -> Option<&BookEntry> {
+        self.entries.get(&(position.player, position.opponent))
+    }
+
+    /// Returns the book moves of `position` sorted by descending score,
+    /// or nothing if the position is not in the book under any symmetry.
+    pub fn get_moves(&self, position: &Position) -> Vec<BookMove> {
+        for symmetry in Symmetry::all() {
+            let Some(entry) = self.get(&position.transform(symmetry)) else {
+                continue;
+            };
+
+            let inverse = symmetry.inverse();
+            let mut moves: Vec<_> = entry
+                .moves()
+                .filter(|book_move| book_move.move_ < 64)
+                .map(|book_move| BookMove {
+                    move_: inverse.apply_square(book_move.move_),
+                    ..book_move
+                })
+                .filter(|book_move| position.is_valid_move(book_move.move_))
+                .collect();
+
+            moves.sort_by_key(|book_move| -book_move.score);
+            return moves;
+        }
+
+        vec![]
+    }
+
+    /// Picks a random book move that scores at most `randomness` discs less than the
+    /// best book move, so a randomness of 0 only picks between equally good moves.
+    pub fn choose_move(
+        &self,
+        position: &Position,
+        randomness: i32,
+        rng: &mut impl Rng,
+    ) -> Option<BookMove> {
+        let moves = self.get_moves(position);
+        let best_score = moves.first()?.score;
+
+        let candidates: Vec<_> = moves
+            .into_iter()
+            .filter(|book_move| book_move.score >= best_score - randomness)
+            .collect();
+
+        candidates.choose(rng).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::othello::squares::*;
+
+    fn entry(links: &[(usize, i32)], leaf: Option<(usize, i32)>) -> BookEntry {
+        let book_move = |(move_, score)| BookMove { move_, score };
+
+        BookEntry {
+            links: links.iter().copied().map(book_move).collect(),
+            leaf: leaf.map(book_move),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_get_moves() {
+        let mut book = Book::new();
+        let position = Position::new();
+        book.insert(position, entry(&[(D3, -2), (F5, 0)], Some((C4, 1))));
+
+        assert_eq!(book.len(), 1);
+        assert_eq!(
+            book.get_moves(&position),
+            vec![
+                BookMove {
+                    move_: C4,
+                    score: 1
+                },
+                BookMove {
+                    move_: F5,
+                    score: 0
+                },
+                BookMove {
+                    move_: D3,
+                    score: -2
+                },
+            ]
+        );
+
+        // Moves of a symmetric position are mapped back
+        let child = position.do_move_cloned(D3);
+        book.insert(child, entry(&[(C3, 2), (E3, 0)], None));
+
+        let mirrored = position.do_move_cloned(E6);
+        let moves: Vec<_> = book.get_moves(&mirrored).iter().map(|m| m.move_).collect();
+        assert_eq!(moves, vec![F6, D6]);
+
+        assert!(book
+            .get_moves(&position.do_move_cloned(C4).do_move_cloned(C3))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_choose_move() {
+        let mut book = Book::new();
+        let position = Position::new();
+        book.insert(
+            position,
+            entry(&[(D3, 0), (C4, 0), (F5, -3)], Some((E6, -6))),
+        );
+
+        let mut rng = rand::thread_rng();
+        let mut chosen = std::collections::HashSet::new();
+        for _ in 0..200 {
+            let randomness = 3;
+            chosen.insert(
+                book.choose_move(&position, randomness, &mut rng)
+                    .unwrap()
+                    .move_,
+            );
+        }
+        assert_eq!(chosen, [D3, C4, F5].into_iter().collect());
+
+        for _ in 0..20 {
+            let book_move = book.choose_move(&position, 0, &mut rng).unwrap();
+            assert!(book_move.move_ == D3 || book_move.move_ == C4);
+        }
+
+        assert!(book
+            .choose_move(&position.do_move_cloned(D3), 0, &mut rng)
+            .is_none());
+    }
+}
@@ -1,17 +1,111 @@
-use crate::bot::squared::endgame::EndgameSearch;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::bot::hash_table::{HashTable, DEFAULT_HASH_TABLE_BITS};
 use crate::othello::position::Position;
 
-use crate::bot::Bot;
+use crate::bot::{Bot, SearchEvent, SearchLimits, SearchObserver, SearchResult, Selectivity};
 
+use super::endgame::EndgameSearch;
 use super::midgame::MidgameSearch;
 
-pub struct EdaxBot;
+pub struct EdaxBot {
+    hash_table: HashTable,
+
+    /// Number of threads searching in parallel
+    threads: usize,
+}
 
 pub const MIDGAME_DEPTH: u32 = 10;
+pub const MIDGAME_SELECTIVITY: Selectivity = Selectivity::Level73;
 pub const ENDGAME_DEPTH: u32 = 18;
 
+impl Default for EdaxBot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EdaxBot {
+    /// Creates a bot that searches with one thread
+    pub fn new() -> Self {
+        Self::with_threads(1)
+    }
+
+    /// Creates a bot that searches with `threads` threads sharing its hash table
+    pub fn with_threads(threads: usize) -> Self {
+        Self {
+            hash_table: HashTable::new(DEFAULT_HASH_TABLE_BITS),
+            threads: threads.max(1),
+        }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+}
+
+/// Runs `search` on `threads` threads sharing one hash table (Lazy SMP).
+///
+/// `search` receives the index of its thread. The main thread has index 0, respects
+/// `limits` and reports to `observer`. Helper threads only contribute through the hash
+/// table and are stopped once the main thread is done or the node limit is reached by
+/// all threads together. The nodes of all threads are counted in the result.
+pub fn search_parallel(
+    threads: usize,
+    limits: &SearchLimits,
+    observer: &mut dyn SearchObserver,
+    search: impl Fn(usize, &SearchLimits, &mut dyn SearchObserver) -> SearchResult + Sync,
+) -> SearchResult {
+    if threads <= 1 {
+        return search(0, limits, observer);
+    }
+
+    let node_counter = Some(Arc::new(AtomicU64::new(0)));
+    let main_limits = SearchLimits {
+        node_counter: node_counter.clone(),
+        ..limits.clone()
+    };
+
+    let helper_stop = Arc::new(AtomicBool::new(false));
+    let helper_limits = SearchLimits {
+        time: None,
+        stop: Some(helper_stop.clone()),
+        node_counter,
+        ..limits.clone()
+    };
+
+    thread::scope(|scope| {
+        let helpers: Vec<_> = (1..threads)
+            .map(|thread_index| {
+                let search = &search;
+                let helper_limits = &helper_limits;
+
+                scope.spawn(move || {
+                    search(thread_index, helper_limits, &mut |_: SearchEvent| {}).nodes
+                })
+            })
+            .collect();
+
+        let mut result = search(0, &main_limits, observer);
+        helper_stop.store(true, Ordering::Relaxed);
+
+        for helper in helpers {
+            result.nodes += helper.join().unwrap();
+        }
+
+        result
+    })
+}
+
 impl Bot for EdaxBot {
-    fn get_move(&mut self, position: &Position) -> usize {
+    fn search(
+        &mut self,
+        position: &Position,
+        limits: &SearchLimits,
+        observer: &mut dyn SearchObserver,
+    ) -> SearchResult {
         let moves = position.get_moves();
 
         if moves == 0 {
@@ -19,14 +113,125 @@ impl Bot for EdaxBot {
         }
 
         if moves.count_ones() == 1 {
-            return moves.trailing_zeros() as usize;
+            return SearchResult::unsearched(moves.trailing_zeros() as usize);
         }
 
+        self.hash_table.new_search();
+        let hash_table = &self.hash_table;
+
         if position.count_empty() > ENDGAME_DEPTH {
-            let mut search = MidgameSearch::new(*position);
-            return search.get_move();
+            return search_parallel(
+                self.threads,
+                limits,
+                observer,
+                |thread_index, limits, observer| {
+                    let mut search = MidgameSearch::new(*position, hash_table);
+                    search.set_thread_index(thread_index);
+                    search.search(limits, observer)
+                },
+            );
         }
 
-        EndgameSearch::new().get_move(position)
+        search_parallel(
+            self.threads,
+            limits,
+            observer,
+            |thread_index, limits, observer| {
+                let mut search = EndgameSearch::new(*position, hash_table);
+                search.set_thread_index(thread_index);
+                search.search(limits, observer)
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::NODES_PER_CHECK;
+    use crate::othello::ffo_problems::parse_ffo_problems;
+    use crate::othello::position::XOT_POSITIONS;
+
+    #[test]
+    fn test_with_threads() {
+        assert_eq!(EdaxBot::with_threads(4).threads(), 4);
+        assert_eq!(EdaxBot::with_threads(0).threads(), 1);
+        assert_eq!(EdaxBot::new().threads(), 1);
+    }
+
+    #[test]
+    fn test_parallel_endgame() {
+        let problem = parse_ffo_problems()
+            .into_iter()
+            .find(|p| p.depth <= 14)
+            .unwrap();
+
+        let mut bot = EdaxBot::with_threads(4);
+        let result = bot.search(
+            &problem.position,
+            &SearchLimits::default(),
+            &mut |_: SearchEvent| {},
+        );
+
+        let best_score = *problem.solutions.values().max().unwrap();
+        assert_eq!(result.score, best_score);
+        assert_eq!(problem.solutions[&result.best_move], best_score);
+        assert_eq!(result.selectivity, Selectivity::Level100);
+        assert!(result.is_endgame);
+    }
+
+    #[test]
+    fn test_parallel_midgame() {
+        let position = Position::new_xot();
+        let limits = SearchLimits {
+            depth: Some(6),
+            ..Default::default()
+        };
+
+        let mut depths = vec![];
+        let parallel =
+            EdaxBot::with_threads(4).search(&position, &limits, &mut |event: SearchEvent| {
+                if let SearchEvent::DepthCompleted(result) = event {
+                    depths.push(result.depth);
+                }
+            });
+
+        // Only the main thread reports, helpers add their nodes
+        assert_eq!(depths, (1..=6).collect::<Vec<_>>());
+        assert!(position.is_valid_move(parallel.best_move));
+        assert!(parallel.nodes > 0);
+    }
+
+    #[test]
+    fn test_parallel_node_limit() {
+        let position = XOT_POSITIONS[0];
+        let limits = SearchLimits {
+            depth: Some(60),
+            nodes: Some(100_000),
+            ..Default::default()
+        };
+
+        // Helpers count towards the limit, overrunning it by at most a batch of nodes each
+        let threads = 4;
+        let result =
+            EdaxBot::with_threads(threads).search(&position, &limits, &mut |_: SearchEvent| {});
+        assert!(position.is_valid_move(result.best_move));
+        assert!(result.nodes >= 100_000);
+        assert!(result.nodes <= 100_000 + threads as u64 * NODES_PER_CHECK);
+    }
+
+    #[test]
+    fn test_parallel_stop() {
+        let position = Position::new();
+        let stop = Arc::new(AtomicBool::new(true));
+        let limits = SearchLimits {
+            depth: Some(60),
+            stop: Some(stop),
+            ..Default::default()
+        };
+
+        // Helpers stop with the main thread, even though they have no limits of their own
+        let result = EdaxBot::with_threads(4).search(&position, &limits, &mut |_: SearchEvent| {});
+        assert!(position.is_valid_move(result.best_move));
     }
 }
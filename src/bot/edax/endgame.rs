@@ -0,0 +1,838 @@
+// This is adapted from https://github.com/abulmo/edax-reversi/blob/master/src/endgame.c
+
+use crate::bot::hash_table::{HashTable, NO_MOVE};
+use crate::bot::move_ordering::{MoveList, ScoredMove, W_PARITY};
+use crate::bot::{
+    RootMoveScore, SearchBudget, SearchEvent, SearchLimits, SearchObserver, SearchResult,
+    Selectivity,
+};
+use crate::othello::do_move::get_flipped;
+use crate::othello::position::Position;
+
+use super::eval::{Eval, SCORE_MAX, SCORE_MIN};
+use super::midgame::MidgameSearch;
+
+/// Positions with this many empties or fewer are searched without hash table and move sorting
+const DEPTH_TO_SHALLOW_SEARCH: u32 = 7;
+
+/// Positions with at least this many empties also use the evaluation for move ordering
+const EVAL_SORT_EMPTIES: u32 = 14;
+
+/// Minimum number of empties for trying a ProbCut. Like Edax, positions with fewer
+/// empties are solved without it, as the shallow searches cost more than they save.
+const PROBCUT_MIN_EMPTIES: u32 = 15;
+
+/// Stability is only computed when alpha is at least this high, indexed by number of empties
+const STABILITY_THRESHOLD: [i32; 61] = [
+    99, 99, 99, 99, 6, 8, 10, 12, 14, 16, 20, 22, 24, 26, 28, 30, 32, 34, 36, 38, 40, 42, 44, 46,
+    48, 48, 50, 50, 52, 52, 54, 54, 56, 56, 58, 58, 60, 60, 62, 62, 64, 64, 64, 64, 64, 64, 64, 64,
+    64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64, 64,
+];
+
+/// Bit of the quadrant of each square, used to track parity of empty squares per quadrant
+const QUADRANT_ID: [u8; 64] = quadrant_ids();
+
+/// Squares of all quadrants whose bit is set in the index
+const QUADRANT_MASK: [u64; 16] = quadrant_masks();
+
+const fn quadrant_ids() -> [u8; 64] {
+    let mut ids = [0; 64];
+    let mut index = 0;
+    while index < 64 {
+        let right = (index % 8 >= 4) as u8;
+        let bottom = (index / 8 >= 4) as u8;
+        ids[index] = 1 << (right + 2 * bottom);
+        index += 1;
+    }
+    ids
+}
+
+const fn quadrant_masks() -> [u64; 16] {
+    let ids = quadrant_ids();
+    let mut masks = [0; 16];
+    let mut parity = 0;
+    while parity < 16 {
+        let mut index = 0;
+        while index < 64 {
+            if parity & ids[index] as usize != 0 {
+                masks[parity] |= 1 << index;
+            }
+            index += 1;
+        }
+        parity += 1;
+    }
+    masks
+}
+
+pub struct EndgameSearch<'a> {
+    position: Position,
+    n_empties: u32,
+
+    /// Bit per quadrant that is set if the quadrant has an odd number of empties
+    parity: u8,
+
+    nodes: u64,
+    hash_table: &'a HashTable,
+    budget: SearchBudget,
+    aborted: bool,
+    selectivity: Selectivity,
+
+    /// Index of the thread in a parallel search, 0 for the main thread
+    thread_index: usize,
+}
+
+impl<'a> EndgameSearch<'a> {
+    pub fn new(position: Position, hash_table: &'a HashTable) -> Self {
+        let mut search = Self {
+            position,
+            n_empties: 0,
+            parity: 0,
+            nodes: 0,
+            hash_table,
+            budget: SearchBudget::new(&SearchLimits::default()),
+            aborted: false,
+            selectivity: Selectivity::Level100,
+            thread_index: 0,
+        };
+        search.set_position(position);
+        search
+    }
+
+    /// Makes this search a helper thread of a parallel search. Helpers start with another
+    /// root move, so the main thread finds its other root moves solved in the hash table.
+    pub fn set_thread_index(&mut self, thread_index: usize) {
+        self.thread_index = thread_index;
+    }
+
+    fn set_position(&mut self, position: Position) {
+        self.position = position;
+        self.n_empties = position.count_empty();
+        self.parity = 0;
+
+        let mut empties = !(position.player | position.opponent);
+        while empties != 0 {
+            self.parity ^= QUADRANT_ID[empties.trailing_zeros() as usize];
+            empties &= empties - 1;
+        }
+    }
+
+    fn do_move(&mut self, move_: usize, flipped: u64) {
+        self.position.player |= flipped | (1u64 << move_);
+        self.position.opponent ^= flipped;
+        self.position.pass();
+        self.n_empties -= 1;
+        self.parity ^= QUADRANT_ID[move_];
+    }
+
+    fn undo_move(&mut self, move_: usize, flipped: u64) {
+        self.position.undo_move(move_, flipped);
+        self.n_empties += 1;
+        self.parity ^= QUADRANT_ID[move_];
+    }
+
+    /// Counts a node and checks if the budget ran out
+    fn visit_node(&mut self) {
+        self.nodes += 1;
+
+        if self.budget.is_exhausted(self.nodes) {
+            self.aborted = true;
+        }
+    }
+
+    /// Solves the position, starting at the lowest selectivity and raising it up to the
+    /// selectivity of the limits, which is 100% by default. Selective searches are fast
+    /// and fill the hash table with good moves for the next one. If the budget runs out,
+    /// the result of the last completed selectivity is returned, with depth 0 if none was.
+    pub fn search(
+        &mut self,
+        limits: &SearchLimits,
+        observer: &mut dyn SearchObserver,
+    ) -> SearchResult {
+        let root = self.position;
+        let depth = self.n_empties;
+
+        self.budget = SearchBudget::new(limits);
+        self.aborted = false;
+        self.nodes = 0;
+
+        let mut moves: Vec<_> = self
+            .sorted_moves(NO_MOVE as usize)
+            .iter()
+            .map(|scored| (scored.move_, scored.flipped))
+            .collect();
+
+        let mut result = SearchResult::unsearched(moves[0].0);
+        result.is_endgame = true;
+
+        let max_selectivity = limits.selectivity.unwrap_or(Selectivity::Level100);
+
+        for selectivity in Selectivity::ALL {
+            if selectivity > max_selectivity {
+                break;
+            }
+            self.selectivity = selectivity;
+
+            // Search the best move of the previous selectivity first
+            moves.sort_by_key(|(move_, _)| *move_ != result.best_move);
+
+            if self.thread_index > 0 {
+                let shift = self.thread_index % moves.len();
+                moves.rotate_left(shift);
+            }
+
+            let Some(root_moves) = self.search_root(&moves, observer) else {
+                break;
+            };
+
+            // Each move that raised alpha has an exact score, the last one is the best
+            let best = *root_moves.iter().rev().find(|m| !m.is_upper_bound).unwrap();
+
+            result.best_move = best.move_;
+            result.score = best.score;
+            result.depth = depth;
+            result.selectivity = selectivity;
+            result.principal_variation =
+                self.hash_table
+                    .principal_variation(&root, best.move_, depth as usize);
+            result.root_moves = root_moves;
+            result.nodes = self.nodes;
+            result.duration = self.budget.elapsed();
+
+            observer.notify(SearchEvent::DepthCompleted(&result));
+        }
+
+        result.nodes = self.nodes;
+        result.duration = self.budget.elapsed();
+
+        self.set_position(root);
+        result
+    }
+
+    /// Returns the scores of all moves, or `None` if the budget ran out.
+    fn search_root(
+        &mut self,
+        moves: &[(usize, u64)],
+        observer: &mut dyn SearchObserver,
+    ) -> Option<Vec<RootMoveScore>> {
+        let depth = self.n_empties;
+        let mut root_moves = Vec::with_capacity(moves.len());
+
+        // Below any possible score, so the first move always gets an exact score
+        let mut alpha = SCORE_MIN - 1;
+
+        for (i, &(move_, flipped)) in moves.iter().enumerate() {
+            self.do_move(move_, flipped);
+
+            let mut score = if i == 0 {
+                -self.solve(-SCORE_MAX, -alpha)
+            } else {
+                -self.solve(-alpha - 1, -alpha)
+            };
+
+            if i != 0 && score > alpha && !self.aborted {
+                score = -self.solve(-SCORE_MAX, -alpha);
+            }
+
+            self.undo_move(move_, flipped);
+
+            if self.aborted {
+                return None;
+            }
+
+            let root_move = RootMoveScore {
+                move_,
+                score: score as isize,
+                is_upper_bound: score <= alpha,
+            };
+            root_moves.push(root_move);
+            observer.notify(SearchEvent::RootMove { depth, root_move });
+
+            if score > alpha {
+                alpha = score;
+            }
+        }
+
+        Some(root_moves)
+    }
+
+    /// Tries to prove with a shallow midgame search that the score of a null window
+    /// search is outside of the window, as in Edax's Multi-ProbCut.
+    /// Returns the bound that the full search would return in that case.
+    /// The shallow searches spend from the budget of this search.
+    fn probcut(&mut self, alpha: i32) -> Option<i32> {
+        let beta = alpha + 1;
+        let depth = self.n_empties;
+        let probcut_depth = 2 * (depth / 4) + (depth & 1);
+
+        let sigma = Eval::eval_sigma(depth as i32, depth as i32, probcut_depth as i32);
+        let error = (self.selectivity.probcut_t() * sigma + 0.5) as i32;
+        let eval = Eval::new(&self.position).score(depth);
+
+        let budget = std::mem::replace(
+            &mut self.budget,
+            SearchBudget::new(&SearchLimits::default()),
+        );
+        let mut midgame =
+            MidgameSearch::with_budget(self.position, self.hash_table, budget, self.nodes);
+        let mut bound = None;
+
+        let probcut_beta = beta + error;
+        if eval >= alpha && probcut_beta < SCORE_MAX {
+            let score = midgame.null_window_search(probcut_depth, probcut_beta - 1);
+            if score >= probcut_beta {
+                bound = Some(beta);
+            }
+        }
+
+        let probcut_alpha = alpha - error;
+        if bound.is_none() && eval < beta && probcut_alpha > SCORE_MIN {
+            let score = midgame.null_window_search(probcut_depth, probcut_alpha);
+            if score <= probcut_alpha {
+                bound = Some(alpha);
+            }
+        }
+
+        let (budget, nodes, aborted) = midgame.into_budget();
+        self.budget = budget;
+        self.nodes = nodes;
+
+        // Scores of an aborted search prove nothing
+        if aborted {
+            self.aborted = true;
+            return None;
+        }
+        bound
+    }
+
+    /// Returns the exact score if it is inside (`alpha`, `beta`), otherwise a bound.
+    fn solve(&mut self, alpha: i32, beta: i32) -> i32 {
+        match self.n_empties {
+            0 => {
+                self.visit_node();
+                self.position.final_score() as i32
+            }
+            1..=4 => {
+                let mut squares = [0; 4];
+                let mut empties = !(self.position.player | self.position.opponent);
+                for square in squares.iter_mut().take(self.n_empties as usize) {
+                    *square = empties.trailing_zeros() as usize;
+                    empties &= empties - 1;
+                }
+
+                match self.n_empties {
+                    1 => self.solve_1(squares[0]),
+                    2 => self.solve_2(squares[0], squares[1], alpha, beta),
+                    3 => self.solve_3(squares[0], squares[1], squares[2], alpha, beta),
+                    _ => self.solve_4(squares, alpha, beta),
+                }
+            }
+            n_empties if n_empties <= DEPTH_TO_SHALLOW_SEARCH => self.search_shallow(alpha, beta),
+            _ => self.search_deep(alpha, beta),
+        }
+    }
+
+    /// Returns an upper bound of the score if it is at most `alpha`, based on
+    /// the number of stable discs of the opponent.
+    fn stability_cutoff(&self, alpha: i32) -> Option<i32> {
+        if alpha < STABILITY_THRESHOLD[self.n_empties as usize] {
+            return None;
+        }
+
+        let score = SCORE_MAX - 2 * self.position.count_opponent_stable() as i32;
+
+        if score <= alpha {
+            Some(score)
+        } else {
+            None
+        }
+    }
+
+    /// Scores the game after the player passes, or when neither side can move.
+    fn solve_pass(&mut self, alpha: i32, beta: i32) -> i32 {
+        if self.position.get_opponent_moves() == 0 {
+            return self.position.final_score() as i32;
+        }
+
+        self.position.pass();
+        let score = -self.solve(-beta, -alpha);
+        self.position.pass();
+        score
+    }
+
+    fn solve_1(&mut self, x: usize) -> i32 {
+        self.visit_node();
+
+        let player = self.position.player;
+        let opponent = self.position.opponent;
+        let n_player = player.count_ones() as i32;
+
+        let flipped = get_flipped(player, opponent, x);
+        if flipped != 0 {
+            return 2 * (n_player + flipped.count_ones() as i32 + 1) - 64;
+        }
+
+        let flipped = get_flipped(opponent, player, x);
+        if flipped != 0 {
+            return 2 * (n_player - flipped.count_ones() as i32) - 64;
+        }
+
+        // Neither side can play the last empty, it goes to the winner
+        self.position.final_score() as i32
+    }
+
+    fn solve_2(&mut self, x1: usize, x2: usize, alpha: i32, beta: i32) -> i32 {
+        self.visit_node();
+
+        let player = self.position.player;
+        let opponent = self.position.opponent;
+        let mut best_score = SCORE_MIN - 1;
+
+        let flipped = get_flipped(player, opponent, x1);
+        if flipped != 0 {
+            self.do_move(x1, flipped);
+            best_score = -self.solve_1(x2);
+            self.undo_move(x1, flipped);
+
+            if best_score >= beta {
+                return best_score;
+            }
+        }
+
+        let flipped = get_flipped(player, opponent, x2);
+        if flipped != 0 {
+            self.do_move(x2, flipped);
+            best_score = best_score.max(-self.solve_1(x1));
+            self.undo_move(x2, flipped);
+        }
+
+        if best_score == SCORE_MIN - 1 {
+            return self.solve_pass(alpha, beta);
+        }
+
+        best_score
+    }
+
+    fn solve_3(&mut self, x1: usize, x2: usize, x3: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.visit_node();
+
+        let [x1, x2, x3] = self.sort_by_parity([x1, x2, x3]);
+
+        let player = self.position.player;
+        let opponent = self.position.opponent;
+        let mut best_score = SCORE_MIN - 1;
+
+        for (x, rest) in [(x1, [x2, x3]), (x2, [x1, x3]), (x3, [x1, x2])] {
+            let flipped = get_flipped(player, opponent, x);
+            if flipped == 0 {
+                continue;
+            }
+
+            self.do_move(x, flipped);
+            let score = -self.solve_2(rest[0], rest[1], -beta, -alpha);
+            self.undo_move(x, flipped);
+
+            if score > best_score {
+                best_score = score;
+                if score >= beta {
+                    return score;
+                }
+                alpha = alpha.max(score);
+            }
+        }
+
+        if best_score == SCORE_MIN - 1 {
+            return self.solve_pass(alpha, beta);
+        }
+
+        best_score
+    }
+
+    fn solve_4(&mut self, squares: [usize; 4], mut alpha: i32, beta: i32) -> i32 {
+        self.visit_node();
+
+        if let Some(score) = self.stability_cutoff(alpha) {
+            return score;
+        }
+
+        let [x1, x2, x3, x4] = self.sort_by_parity(squares);
+
+        let player = self.position.player;
+        let opponent = self.position.opponent;
+        let mut best_score = SCORE_MIN - 1;
+
+        for (x, rest) in [
+            (x1, [x2, x3, x4]),
+            (x2, [x1, x3, x4]),
+            (x3, [x1, x2, x4]),
+            (x4, [x1, x2, x3]),
+        ] {
+            let flipped = get_flipped(player, opponent, x);
+            if flipped == 0 {
+                continue;
+            }
+
+            self.do_move(x, flipped);
+            let score = -self.solve_3(rest[0], rest[1], rest[2], -beta, -alpha);
+            self.undo_move(x, flipped);
+
+            if score > best_score {
+                best_score = score;
+                if score >= beta {
+                    return score;
+                }
+                alpha = alpha.max(score);
+            }
+        }
+
+        if best_score == SCORE_MIN - 1 {
+            return self.solve_pass(alpha, beta);
+        }
+
+        best_score
+    }
+
+    /// Puts squares in quadrants with an odd number of empties first
+    fn sort_by_parity<const N: usize>(&self, mut squares: [usize; N]) -> [usize; N] {
+        squares.sort_by_key(|&x| self.parity & QUADRANT_ID[x] == 0);
+        squares
+    }
+
+    /// Alpha-beta search without hash table, trying moves in odd quadrants first.
+    fn search_shallow(&mut self, mut alpha: i32, beta: i32) -> i32 {
+        self.visit_node();
+
+        if let Some(score) = self.stability_cutoff(alpha) {
+            return score;
+        }
+
+        let moves = self.position.get_moves();
+        if moves == 0 {
+            return self.solve_pass(alpha, beta);
+        }
+
+        let odd_moves = moves & QUADRANT_MASK[self.parity as usize];
+        let mut best_score = SCORE_MIN - 1;
+
+        for mut remaining_moves in [odd_moves, moves & !odd_moves] {
+            while remaining_moves != 0 {
+                let move_ = remaining_moves.trailing_zeros() as usize;
+                remaining_moves &= remaining_moves - 1;
+
+                let flipped = get_flipped(self.position.player, self.position.opponent, move_);
+
+                self.do_move(move_, flipped);
+                let score = -self.solve(-beta, -alpha);
+                self.undo_move(move_, flipped);
+
+                if self.aborted {
+                    return 0;
+                }
+
+                if score > best_score {
+                    best_score = score;
+                    if score >= beta {
+                        return score;
+                    }
+                    alpha = alpha.max(score);
+                }
+            }
+        }
+
+        best_score
+    }
+
+    /// Returns the valid moves sorted for fastest-first search: the hash move comes
+    /// first, followed by moves leaving the opponent with the fewest moves.
+    fn sorted_moves(&self, hash_move: usize) -> MoveList {
+        let mut moves = MoveList::new(&self.position);
+        moves.evaluate(&self.position, hash_move);
+
+        if self.n_empties < 12 {
+            moves.add_bonus(QUADRANT_MASK[self.parity as usize], W_PARITY);
+        }
+
+        if self.n_empties >= EVAL_SORT_EMPTIES {
+            let position = self.position;
+            let n_empties = self.n_empties - 1;
+
+            moves.add_search_scores(|move_, flipped| {
+                let next = Position::new_from_bitboards(
+                    position.opponent ^ flipped,
+                    position.player | flipped | (1u64 << move_),
+                );
+                -Eval::new(&next).score(n_empties)
+            });
+        }
+
+        moves.sort();
+        moves
+    }
+
+    /// Principal variation search with hash table, stability cutoff and fastest-first move ordering.
+    fn search_deep(&mut self, mut alpha: i32, beta: i32) -> i32 {
+        self.visit_node();
+
+        if self.aborted {
+            return 0;
+        }
+
+        if let Some(score) = self.stability_cutoff(alpha) {
+            return score;
+        }
+
+        if !self.position.has_moves() {
+            return self.solve_pass(alpha, beta);
+        }
+
+        // Midgame entries are not exact, even when searched as deep as the number of
+        // empties, such as those of the ProbCut searches
+        let mut hash_move = NO_MOVE as usize;
+        if let Some(entry) = self.hash_table.get(&self.position) {
+            if entry.covers_endgame(self.selectivity) {
+                if entry.lower >= beta {
+                    return entry.lower;
+                }
+                if entry.upper <= alpha {
+                    return entry.upper;
+                }
+                if entry.lower == entry.upper {
+                    return entry.lower;
+                }
+            }
+            hash_move = entry.best_move as usize;
+        }
+
+        if beta == alpha + 1
+            && self.n_empties >= PROBCUT_MIN_EMPTIES
+            && self.selectivity < Selectivity::Level100
+        {
+            if let Some(bound) = self.probcut(alpha) {
+                return bound;
+            }
+        }
+
+        let moves = self.sorted_moves(hash_move);
+
+        let original_alpha = alpha;
+        let mut best_score = SCORE_MIN - 1;
+        let mut best_move = NO_MOVE as usize;
+
+        for (i, &ScoredMove { move_, flipped, .. }) in moves.iter().enumerate() {
+            self.do_move(move_, flipped);
+
+            let score = if i == 0 {
+                -self.solve(-beta, -alpha)
+            } else {
+                // Null window search, only re-search if the move is better than expected
+                let score = -self.solve(-alpha - 1, -alpha);
+                if score > alpha && score < beta && !self.aborted {
+                    -self.solve(-beta, -alpha)
+                } else {
+                    score
+                }
+            };
+
+            self.undo_move(move_, flipped);
+
+            if self.aborted {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = move_;
+
+                if score >= beta {
+                    break; // Beta cutoff
+                }
+                alpha = alpha.max(score);
+            }
+        }
+
+        self.hash_table.store_endgame(
+            &self.position,
+            self.selectivity,
+            original_alpha,
+            beta,
+            best_score,
+            best_move,
+        );
+
+        best_score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::format_nodes;
+    use crate::othello::ffo_problems::parse_ffo_problems;
+
+    /// Slow reference solver
+    fn negamax(position: &Position) -> i32 {
+        let children = position.children();
+
+        if children.is_empty() {
+            let mut passed = *position;
+            passed.pass();
+
+            if !passed.has_moves() {
+                return position.final_score() as i32;
+            }
+            return -negamax(&passed);
+        }
+
+        children.iter().map(|child| -negamax(child)).max().unwrap()
+    }
+
+    #[test]
+    fn test_quadrants() {
+        assert_eq!(QUADRANT_ID[0], 1);
+        assert_eq!(QUADRANT_ID[7], 2);
+        assert_eq!(QUADRANT_ID[56], 4);
+        assert_eq!(QUADRANT_ID[63], 8);
+        assert_eq!(QUADRANT_MASK[1], 0x000000000f0f0f0f);
+        assert_eq!(QUADRANT_MASK[2], 0x00000000f0f0f0f0);
+        assert_eq!(QUADRANT_MASK[4], 0x0f0f0f0f00000000);
+        assert_eq!(QUADRANT_MASK[8], 0xf0f0f0f000000000);
+        assert_eq!(QUADRANT_MASK[15], u64::MAX);
+    }
+
+    #[test]
+    fn test_solve_small() {
+        for problem in parse_ffo_problems().iter().filter(|p| p.depth <= 14) {
+            // Play moves until few empties are left
+            let mut position = problem.position;
+            while position.count_empty() > 9 && position.has_moves() {
+                let move_ = position.get_moves().trailing_zeros() as usize;
+                position.do_move(move_);
+            }
+
+            if !position.has_moves() {
+                continue;
+            }
+
+            let hash_table = HashTable::new(10);
+            let mut search = EndgameSearch::new(position, &hash_table);
+            for (alpha, beta) in [(SCORE_MIN, SCORE_MAX), (-1, 1), (10, 20), (-20, -10)] {
+                let expected = negamax(&position);
+                let score = search.solve(alpha, beta);
+
+                if expected <= alpha {
+                    assert!(score <= alpha);
+                } else if expected >= beta {
+                    assert!(score >= beta);
+                } else {
+                    assert_eq!(score, expected);
+                }
+                assert_eq!(search.position, position);
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_result() {
+        let problem = parse_ffo_problems()
+            .into_iter()
+            .find(|p| p.depth <= 12)
+            .unwrap();
+
+        let hash_table = HashTable::new(10);
+        let mut search = EndgameSearch::new(problem.position, &hash_table);
+
+        let mut selectivities = vec![];
+        let result = search.search(&SearchLimits::default(), &mut |event: SearchEvent| {
+            if let SearchEvent::DepthCompleted(result) = event {
+                selectivities.push(result.selectivity);
+            }
+        });
+
+        assert_eq!(selectivities, Selectivity::ALL);
+        assert_eq!(result.selectivity, Selectivity::Level100);
+
+        let best_score = *problem.solutions.values().max().unwrap();
+        assert_eq!(result.score, best_score);
+        assert_eq!(problem.solutions[&result.best_move], best_score);
+        assert_eq!(result.depth, problem.depth);
+        assert!(result.is_endgame);
+        assert_eq!(result.root_moves.len(), problem.solutions.len());
+        assert_eq!(result.principal_variation[0], result.best_move);
+        assert_eq!(search.position, problem.position);
+    }
+
+    #[test]
+    fn test_node_count() {
+        // Guards move ordering against regressions, without eval sorting and probcut
+        // the node counts don't depend on the eval weights.
+        let expected_nodes = [8623, 15040, 18869];
+
+        for (problem, expected_nodes) in parse_ffo_problems().iter().zip(expected_nodes) {
+            let mut position = problem.position;
+            while position.count_empty() > 12 {
+                position.do_move(position.get_moves().trailing_zeros() as usize);
+            }
+
+            let hash_table = HashTable::new(16);
+            let mut search = EndgameSearch::new(position, &hash_table);
+            search.solve(SCORE_MIN, SCORE_MAX);
+
+            assert_eq!(search.nodes, expected_nodes);
+        }
+    }
+
+    #[test]
+    fn test_probcut_node_limit() {
+        // The shallow searches of ProbCut stop with the endgame search instead of finishing
+        let problem = parse_ffo_problems()
+            .into_iter()
+            .find(|p| p.depth >= 20)
+            .unwrap();
+        let limits = SearchLimits {
+            nodes: Some(100),
+            ..Default::default()
+        };
+
+        let hash_table = HashTable::new(16);
+        let mut search = EndgameSearch::new(problem.position, &hash_table);
+        search.budget = SearchBudget::new(&limits);
+        search.selectivity = Selectivity::Level73;
+
+        assert_eq!(search.probcut(0), None);
+        assert!(search.aborted);
+        assert_eq!(search.nodes, 100);
+    }
+
+    #[test]
+    fn test_ffo_problems() {
+        if std::env::var("RUN_FFO_TESTS").is_err() {
+            println!("Skipping FFO tests. Set RUN_FFO_TESTS environment variable to run them.");
+            return;
+        }
+
+        // FFO endgame test suite positions #40 to #59
+        let problems: Vec<_> = parse_ffo_problems()
+            .into_iter()
+            .filter(|p| (40..=59).contains(&p.line_number))
+            .collect();
+
+        let hash_table = HashTable::new(22);
+
+        for problem in &problems {
+            let mut search = EndgameSearch::new(problem.position, &hash_table);
+            let result = search.search(&SearchLimits::default(), &mut |_: SearchEvent| {});
+
+            println!(
+                "FFO #{:2}, depth {:2}: score {:3} | {} / {:.3}s",
+                problem.line_number,
+                problem.depth,
+                result.score,
+                format_nodes(result.nodes),
+                result.duration.as_secs_f64(),
+            );
+
+            let best_score = *problem.solutions.values().max().unwrap();
+            assert_eq!(result.score, best_score);
+            assert_eq!(problem.solutions[&result.best_move], best_score);
+        }
+    }
+}
@@ -3,9 +3,15 @@
 use crate::othello::{position::Position, squares::*};
 use lazy_static::lazy_static;
 
+use super::weights::{load_weights, EVAL_WEIGHT};
+
 /// The number of features in the evaluation
 pub const EVAL_N_FEATURES: usize = 47;
 
+/// Bounds of exact scores, evaluations are strictly between them
+pub const SCORE_MIN: i32 = -64;
+pub const SCORE_MAX: i32 = 64;
+
 lazy_static! {
     pub static ref EVAL_F2X: [Vec<usize>; EVAL_N_FEATURES] = [
         vec![A1, B1, A2, B2, C1, A3, C2, B3, C3],
@@ -145,6 +151,40 @@ pub const EVAL_MAX_VALUE: [i32; EVAL_N_FEATURES] = [
     225989, 226232, 226232, 226232, 226232, 226313, 226313, 226313, 226313, 226314,
 ];
 
+/// Contribution of one feature to an evaluation
+#[derive(Clone, PartialEq, Debug)]
+pub struct FeatureContribution {
+    /// Index of the feature in `EVAL_F2X`
+    pub feature: usize,
+
+    /// Squares of the pattern, empty for the constant feature
+    pub squares: &'static [usize],
+
+    /// Index of the weight of the pattern's configuration among those of the ply
+    pub index: usize,
+
+    /// Weight of the configuration, in 128ths of a disc
+    pub weight: i32,
+}
+
+/// Evaluation of a position broken down by feature, for the player to move
+#[derive(Clone, PartialEq, Debug)]
+pub struct Explanation {
+    pub features: Vec<FeatureContribution>,
+
+    /// Sum of the weights, in 128ths of a disc
+    pub total: i32,
+
+    /// Evaluation in discs as returned by `Eval::score`, the total rounded and clamped
+    pub score: i32,
+}
+
+/// Returns the evaluation of `position` for the player to move with the contribution
+/// of each pattern, or an error if the weights can't be loaded
+pub fn explain(position: &Position) -> Result<Explanation, String> {
+    Eval::new(position).explain(position.count_empty())
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct Eval {
     /// The features of the position
@@ -277,6 +317,65 @@ impl Eval {
         self.swap();
     }
 
+    /// Returns the evaluation of the position for the player to move, in discs.
+    pub fn score(&self, n_empties: u32) -> i32 {
+        let player_index = self.player as usize;
+        let empty_index = (60 - n_empties) as usize;
+
+        let w = &EVAL_WEIGHT[player_index][empty_index];
+        let f = &self.features;
+
+        let mut score = 0;
+        for i in 0..EVAL_N_FEATURES {
+            score += w[f[i] as usize] as i32;
+        }
+
+        Self::round_score(score)
+    }
+
+    /// Returns the evaluation in discs of a sum of weights, which are in 128ths of a disc
+    fn round_score(mut score: i32) -> i32 {
+        if score > 0 {
+            score += 64;
+        } else {
+            score -= 64;
+        }
+        score /= 128;
+
+        if score <= SCORE_MIN {
+            score = SCORE_MIN + 1;
+        } else if score >= SCORE_MAX {
+            score = SCORE_MAX - 1;
+        }
+
+        score
+    }
+
+    /// Returns the evaluation of the position with the contribution of each feature,
+    /// or an error if the weights can't be loaded
+    pub fn explain(&self, n_empties: u32) -> Result<Explanation, String> {
+        let weights = &load_weights()?[self.player as usize][(60 - n_empties) as usize];
+
+        let features: Vec<FeatureContribution> = (0..EVAL_N_FEATURES)
+            .map(|feature| {
+                let index = self.features[feature] as usize;
+                FeatureContribution {
+                    feature,
+                    squares: &EVAL_F2X[feature],
+                    index,
+                    weight: weights[index] as i32,
+                }
+            })
+            .collect();
+
+        let total = features.iter().map(|feature| feature.weight).sum();
+        Ok(Explanation {
+            features,
+            total,
+            score: Self::round_score(total),
+        })
+    }
+
     pub fn eval_sigma(n_empty: i32, depth: i32, probcut_depth: i32) -> f64 {
         let sigma = -0.10026799 * n_empty as f64
             + 0.31027733 * depth as f64
@@ -317,4 +416,26 @@ mod tests {
 
         assert_eq!(eval, initial_eval);
     }
+
+    #[test]
+    fn test_explain() {
+        let mut position = Position::new();
+        for move_ in [F5, D6, C3, D3, C4] {
+            let explanation = explain(&position).unwrap();
+            let eval = Eval::new(&position);
+
+            assert_eq!(explanation.features.len(), EVAL_N_FEATURES);
+            assert_eq!(explanation.score, eval.score(position.count_empty()));
+            assert_eq!(
+                explanation.total,
+                explanation.features.iter().map(|f| f.weight).sum::<i32>()
+            );
+
+            let corner = &explanation.features[0];
+            assert_eq!(corner.squares, [A1, B1, A2, B2, C1, A3, C2, B3, C3]);
+            assert_eq!(corner.index, eval.features[0] as usize);
+
+            position.do_move(move_);
+        }
+    }
 }
@@ -1,34 +1,80 @@
-use std::time::{Duration, Instant};
-
-use crate::bot::{print_move_stats, print_search_header, print_total_stats};
+use crate::bot::hash_table::{HashTable, NO_MOVE};
+use crate::bot::move_ordering::{sort_depth, MoveList};
+use crate::bot::{
+    RootMoveScore, SearchBudget, SearchEvent, SearchLimits, SearchObserver, SearchResult,
+    Selectivity,
+};
 use crate::othello::position::Position;
 
-use super::bot::MIDGAME_DEPTH;
-use super::eval::{Eval, EVAL_N_FEATURES};
-use super::weights::EVAL_WEIGHT;
+use super::bot::{MIDGAME_DEPTH, MIDGAME_SELECTIVITY};
+use super::eval::{Eval, SCORE_MAX, SCORE_MIN};
+
+/// Half width of the initial window around the score of the previous iteration
+const ASPIRATION_WINDOW: i32 = 4;
 
-const SCORE_MIN: i32 = -64;
-const SCORE_MAX: i32 = 64;
+/// Minimum remaining depth for trying a ProbCut
+const PROBCUT_MIN_DEPTH: u32 = 3;
 
-pub struct MidgameSearch {
+pub struct MidgameSearch<'a> {
     position: Position,
     eval: Eval,
     n_empties: u32,
     nodes: u64,
-}
+    hash_table: &'a HashTable,
+    budget: SearchBudget,
+    aborted: bool,
+    selectivity: Selectivity,
 
-impl MidgameSearch {
-    // TODO #6 bring better midgame search from Edax
+    /// Index of the thread in a parallel search, 0 for the main thread
+    thread_index: usize,
+}
 
-    pub fn new(position: Position) -> Self {
+impl<'a> MidgameSearch<'a> {
+    pub fn new(position: Position, hash_table: &'a HashTable) -> Self {
         Self {
             position,
             eval: Eval::new(&position),
             n_empties: position.count_empty(),
             nodes: 0,
+            hash_table,
+            budget: SearchBudget::new(&SearchLimits::default()),
+            aborted: false,
+            selectivity: Selectivity::Level100,
+            thread_index: 0,
         }
     }
 
+    /// Creates a search that spends from `budget`, continuing the count of `nodes` that
+    /// were searched with it, so searches nested in another one respect its limits
+    pub fn with_budget(
+        position: Position,
+        hash_table: &'a HashTable,
+        budget: SearchBudget,
+        nodes: u64,
+    ) -> Self {
+        Self {
+            nodes,
+            budget,
+            ..Self::new(position, hash_table)
+        }
+    }
+
+    /// Returns the budget with the nodes counted so far, and true if it ran out
+    pub fn into_budget(self) -> (SearchBudget, u64, bool) {
+        (self.budget, self.nodes, self.aborted)
+    }
+
+    /// Makes this search a helper thread of a parallel search. Helpers start with another
+    /// root move and every other helper searches one ply deeper, so that they fill the
+    /// shared hash table with results the main thread hasn't found yet.
+    pub fn set_thread_index(&mut self, thread_index: usize) {
+        self.thread_index = thread_index;
+    }
+
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
     fn do_move(&mut self, move_: usize) -> u64 {
         let flipped = self.position.do_move(move_);
         self.eval.update(move_, flipped);
@@ -47,58 +93,251 @@ impl MidgameSearch {
         self.eval.pass();
     }
 
-    pub fn get_move(&mut self) -> usize {
-        let children = self.position.children_with_index();
-        let mut best_move = children.first().unwrap().0;
-        let mut alpha = SCORE_MIN;
+    /// Searches with iterative deepening until the maximum depth or until the
+    /// budget runs out, returning the result of the last completed iteration.
+    pub fn search(
+        &mut self,
+        limits: &SearchLimits,
+        observer: &mut dyn SearchObserver,
+    ) -> SearchResult {
+        let root = self.position;
+        let mut children = root.children_with_index();
+
+        let mut result = SearchResult::unsearched(children.first().unwrap().0);
+
+        self.budget = SearchBudget::new(limits);
+        self.aborted = false;
+        self.nodes = 0;
+        self.selectivity = limits.selectivity.unwrap_or(MIDGAME_SELECTIVITY);
+
+        let max_depth = self.budget.max_depth(MIDGAME_DEPTH).min(root.count_empty());
+
+        for depth in 1..=max_depth.max(1) {
+            // Search the best move of the previous iteration first
+            children.sort_by_key(|(move_, _)| *move_ != result.best_move);
+
+            let mut depth = depth;
+            if self.thread_index > 0 {
+                let shift = self.thread_index % children.len();
+                children.rotate_left(shift);
+
+                if self.thread_index % 2 == 1 && depth < max_depth {
+                    depth += 1;
+                }
+            }
 
-        let mut total_nodes = 0;
-        let mut total_duration = Duration::ZERO;
+            let Some(root_moves) = self.search_aspiration(&children, depth, &result, observer)
+            else {
+                break;
+            };
+
+            // Each move that raised alpha has an exact score, the last one is the best
+            let best = *root_moves.iter().rev().find(|m| !m.is_upper_bound).unwrap();
+
+            result.best_move = best.move_;
+            result.score = best.score;
+            result.depth = depth;
+            result.selectivity = self.selectivity;
+            result.principal_variation =
+                self.hash_table
+                    .principal_variation(&root, best.move_, depth as usize);
+            result.root_moves = root_moves;
+            result.nodes = self.nodes;
+            result.duration = self.budget.elapsed();
+
+            observer.notify(SearchEvent::DepthCompleted(&result));
+        }
 
-        print_search_header("EdaxBot", false, MIDGAME_DEPTH);
-        for (i, (move_, child)) in children.iter().enumerate() {
-            let start = Instant::now();
+        result.nodes = self.nodes;
+        result.duration = self.budget.elapsed();
+
+        self.position = root;
+        self.eval = Eval::new(&root);
+        self.n_empties = root.count_empty();
+
+        result
+    }
 
+    /// Searches the root at `depth` with a window around the score of the previous
+    /// iteration. If the best score falls outside the window, that side of the window
+    /// is opened and the root is searched again.
+    fn search_aspiration(
+        &mut self,
+        children: &[(usize, Position)],
+        depth: u32,
+        previous: &SearchResult,
+        observer: &mut dyn SearchObserver,
+    ) -> Option<Vec<RootMoveScore>> {
+        // Outside of any possible score, so the first iteration gets exact scores
+        let (mut alpha, mut beta) = (SCORE_MIN - 1, SCORE_MAX + 1);
+
+        if previous.depth > 0 {
+            let score = previous.score as i32;
+            alpha = alpha.max(score - ASPIRATION_WINDOW);
+            beta = beta.min(score + ASPIRATION_WINDOW);
+        }
+
+        loop {
+            let root_moves = self.search_root(children, depth, alpha, beta, observer)?;
+            let best_score = root_moves.iter().map(|m| m.score as i32).max().unwrap();
+
+            if best_score <= alpha {
+                alpha = SCORE_MIN - 1;
+            } else if best_score >= beta {
+                beta = SCORE_MAX + 1;
+            } else {
+                return Some(root_moves);
+            }
+        }
+    }
+
+    /// Returns the scores of all moves at `depth` with window (`alpha`, `beta`), or `None`
+    /// if the budget ran out. Stops after the first move that scores at least `beta`.
+    fn search_root(
+        &mut self,
+        children: &[(usize, Position)],
+        depth: u32,
+        mut alpha: i32,
+        beta: i32,
+        observer: &mut dyn SearchObserver,
+    ) -> Option<Vec<RootMoveScore>> {
+        let mut root_moves = Vec::with_capacity(children.len());
+
+        for (i, (move_, child)) in children.iter().enumerate() {
             self.eval = Eval::new(child);
             self.n_empties = child.count_empty();
             self.position = *child;
 
-            let score = -self.negamax(MIDGAME_DEPTH - 1, -SCORE_MAX, -alpha);
-            let duration = start.elapsed();
-
-            print_move_stats(
-                self.nodes,
-                i,
-                children.len(),
-                score as isize,
-                alpha as isize,
-                duration,
-            );
-            total_nodes += self.nodes;
-            total_duration += duration;
-            self.nodes = 0;
+            let score = if i == 0 {
+                -self.pvs(depth - 1, -beta, -alpha)
+            } else {
+                self.scout(depth - 1, alpha, beta)
+            };
+
+            if self.aborted {
+                return None;
+            }
+
+            let root_move = RootMoveScore {
+                move_: *move_,
+                score: score as isize,
+                is_upper_bound: score <= alpha,
+            };
+            root_moves.push(root_move);
+            observer.notify(SearchEvent::RootMove { depth, root_move });
+
+            if score >= beta {
+                break;
+            }
 
             if score > alpha {
                 alpha = score;
-                best_move = *move_;
             }
         }
 
-        print_total_stats(total_nodes, total_duration);
+        Some(root_moves)
+    }
+
+    /// Searches a move that is expected to be worse than `alpha` with a null window,
+    /// and searches it again with the full window if it turns out to be better.
+    /// Returns the score from the perspective of the player who made the move.
+    fn scout(&mut self, depth: u32, alpha: i32, beta: i32) -> i32 {
+        let score = -self.pvs(depth, -alpha - 1, -alpha);
+
+        if score > alpha && score < beta && !self.aborted {
+            return -self.pvs(depth, -beta, -alpha);
+        }
+
+        score
+    }
+
+    /// Searches the position at `depth` with a null window above `alpha`, without
+    /// selectivity. Returns a score above `alpha` if and only if the position is better.
+    pub fn null_window_search(&mut self, depth: u32, alpha: i32) -> i32 {
+        self.pvs(depth, alpha, alpha + 1)
+    }
+
+    /// Tries to prove with a shallow search that the score of a null window search
+    /// at `depth` is outside of the window, as in Edax's Multi-ProbCut.
+    /// Returns the bound that the full search would return in that case.
+    fn probcut(&mut self, depth: u32, alpha: i32) -> Option<i32> {
+        let beta = alpha + 1;
+
+        let mut probcut_depth = 2 * (depth / 4) + (depth & 1);
+        if probcut_depth == 0 {
+            probcut_depth = depth - 2;
+        }
+
+        let sigma = Eval::eval_sigma(self.n_empties as i32, depth as i32, probcut_depth as i32);
+        let error = (self.selectivity.probcut_t() * sigma + 0.5) as i32;
+        let eval = self.heuristic();
+
+        // The shallow searches are not selective themselves
+        let selectivity = self.selectivity;
+        self.selectivity = Selectivity::Level100;
+
+        let mut bound = None;
+
+        let probcut_beta = beta + error;
+        if eval >= alpha && probcut_beta < SCORE_MAX {
+            let score = self.pvs(probcut_depth, probcut_beta - 1, probcut_beta);
+            if score >= probcut_beta {
+                bound = Some(beta);
+            }
+        }
+
+        let probcut_alpha = alpha - error;
+        if bound.is_none() && eval < beta && probcut_alpha > SCORE_MIN {
+            let score = self.pvs(probcut_depth, probcut_alpha, probcut_alpha + 1);
+            if score <= probcut_alpha {
+                bound = Some(alpha);
+            }
+        }
 
-        best_move
+        self.selectivity = selectivity;
+        bound
     }
 
-    fn negamax(&mut self, depth: u32, mut alpha: i32, beta: i32) -> i32 {
+    /// Returns the valid moves, best first according to the hash move, heuristics
+    /// and a shallow search if `depth` is high enough.
+    fn sorted_moves(&mut self, depth: u32, hash_move: usize) -> MoveList {
+        let mut moves = MoveList::new(&self.position);
+        moves.evaluate(&self.position, hash_move);
+
+        if let Some(sort_depth) = sort_depth(depth) {
+            moves.add_search_scores(|move_, _| {
+                let flipped = self.do_move(move_);
+                let score = -self.pvs(sort_depth, SCORE_MIN, SCORE_MAX);
+                self.undo_move(move_, flipped);
+                score
+            });
+        }
+
+        moves.sort();
+        moves
+    }
+
+    /// Principal variation search: the first move gets the full window,
+    /// the remaining moves are scouted with a null window.
+    fn pvs(&mut self, depth: u32, mut alpha: i32, beta: i32) -> i32 {
+        // Move ordering and ProbCut may start more searches after the budget ran out
+        if self.aborted {
+            return 0;
+        }
+
         self.nodes += 1;
+
+        if self.budget.is_exhausted(self.nodes) {
+            self.aborted = true;
+            return 0;
+        }
+
         if depth == 0 {
             return self.heuristic();
         }
 
-        let mut remaining_moves = self.position.get_moves();
-
         // If no moves available
-        if remaining_moves == 0 {
+        if !self.position.has_moves() {
             // Check if the game is finished
             self.pass();
 
@@ -106,80 +345,117 @@ impl MidgameSearch {
                 // Game is over, return final evaluation
                 let score = self.position.final_score() as i32;
                 self.pass();
-                return score;
+                return -score;
             }
 
             // Recursively evaluate after passing
-            let score = -self.negamax(depth - 1, -beta, -alpha);
+            let score = -self.pvs(depth - 1, -beta, -alpha);
             self.pass();
             return score;
         }
 
-        while remaining_moves != 0 {
-            let move_ = remaining_moves.trailing_zeros() as usize;
-
-            let flipped = self.do_move(move_);
-            let score = -self.negamax(depth - 1, -beta, -alpha);
-            self.undo_move(move_, flipped);
+        let mut hash_move = NO_MOVE as usize;
+        if let Some(entry) = self.hash_table.get(&self.position) {
+            if entry.covers(depth, self.selectivity) {
+                if entry.lower >= beta {
+                    return entry.lower;
+                }
+                if entry.upper <= alpha {
+                    return entry.upper;
+                }
+                if entry.lower == entry.upper {
+                    return entry.lower;
+                }
+            }
+            hash_move = entry.best_move as usize;
+        }
 
-            alpha = alpha.max(score);
+        if beta == alpha + 1
+            && depth >= PROBCUT_MIN_DEPTH
+            && self.selectivity < Selectivity::Level100
+        {
+            let bound = self.probcut(depth, alpha);
 
-            if alpha >= beta {
-                break; // Beta cutoff
+            if self.aborted {
+                return 0;
+            }
+            if let Some(bound) = bound {
+                return bound;
             }
-
-            remaining_moves &= remaining_moves - 1;
         }
 
-        alpha
-    }
+        let original_alpha = alpha;
+        let mut best_score = SCORE_MIN - 1;
+        let mut best_move = NO_MOVE as usize;
 
-    fn heuristic(&self) -> i32 {
-        let player_index = self.eval.player as usize;
-        let empty_index = (60 - self.n_empties) as usize;
+        let moves = self.sorted_moves(depth, hash_move);
+        if self.aborted {
+            return 0;
+        }
 
-        let w = &EVAL_WEIGHT[player_index][empty_index];
-        let f = &self.eval.features;
+        for (i, scored) in moves.iter().enumerate() {
+            let flipped = self.do_move(scored.move_);
+            let score = if i == 0 {
+                -self.pvs(depth - 1, -beta, -alpha)
+            } else {
+                self.scout(depth - 1, alpha, beta)
+            };
+            self.undo_move(scored.move_, flipped);
+
+            if self.aborted {
+                return 0;
+            }
 
-        let mut score = 0;
-        for i in 0..EVAL_N_FEATURES {
-            score += w[f[i] as usize] as i32;
-        }
+            if score > best_score {
+                best_score = score;
+                best_move = scored.move_;
+                alpha = alpha.max(score);
 
-        if score > 0 {
-            score += 64;
-        } else {
-            score -= 64;
+                if alpha >= beta {
+                    break; // Beta cutoff
+                }
+            }
         }
-        score /= 128;
 
-        if score <= SCORE_MIN {
-            score = SCORE_MIN + 1;
-        } else if score >= SCORE_MAX {
-            score = SCORE_MAX - 1;
-        }
+        self.hash_table.store(
+            &self.position,
+            depth,
+            self.selectivity,
+            original_alpha,
+            beta,
+            best_score,
+            best_move,
+        );
+
+        best_score
+    }
 
-        score
+    /// Returns the static evaluation of the position, without searching
+    pub fn heuristic(&self) -> i32 {
+        self.eval.score(self.n_empties)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::othello::position::XOT_POSITIONS;
 
     #[test]
     fn test_new_midgame_search() {
         let position = Position::new();
-        let search = MidgameSearch::new(position);
+        let hash_table = HashTable::new(10);
+        let search = MidgameSearch::new(position, &hash_table);
         assert_eq!(search.n_empties, 60);
     }
 
     #[test]
     fn test_do_and_undo_move() {
         let position = Position::new();
-        let mut search = MidgameSearch::new(position);
+        let hash_table = HashTable::new(10);
+        let mut search = MidgameSearch::new(position, &hash_table);
         let initial_empties = search.n_empties;
-        let initial_board = search.position.clone();
+        let initial_board = search.position;
 
         // Do move
         let move_ = 19; // Valid move for initial position (D3)
@@ -195,18 +471,124 @@ mod tests {
     #[test]
     fn test_get_move_returns_valid_move() {
         let position = Position::new();
-        let mut search = MidgameSearch::new(position);
-        let best_move = search.get_move();
+        let hash_table = HashTable::new(10);
+        let mut search = MidgameSearch::new(position, &hash_table);
+        let best_move = search
+            .search(&SearchLimits::default(), &mut |_: SearchEvent| {})
+            .best_move;
 
         // Check if returned move is valid (one of the four possible initial moves)
-        let valid_initial_moves = vec![19, 26, 37, 44];
+        let valid_initial_moves = [19, 26, 37, 44];
         assert!(valid_initial_moves.contains(&best_move));
     }
 
+    #[test]
+    fn test_get_move_with_node_limit() {
+        let position = Position::new_xot();
+        let hash_table = HashTable::new(10);
+        let mut search = MidgameSearch::new(position, &hash_table);
+
+        let limits = SearchLimits {
+            depth: Some(30),
+            nodes: Some(5000),
+            ..Default::default()
+        };
+        let best_move = search.search(&limits, &mut |_: SearchEvent| {}).best_move;
+
+        assert!(position.is_valid_move(best_move));
+        assert!(search.nodes <= 5000);
+        assert_eq!(search.position, position);
+    }
+
+    /// Plain fixed-depth negamax, without hash table or pruning
+    fn negamax(position: &Position, depth: u32) -> i32 {
+        if depth == 0 {
+            return Eval::new(position).score(position.count_empty());
+        }
+
+        let children = position.children();
+
+        if children.is_empty() {
+            let mut passed = *position;
+            passed.pass();
+
+            if !passed.has_moves() {
+                return position.final_score() as i32;
+            }
+            return -negamax(&passed, depth - 1);
+        }
+
+        children
+            .iter()
+            .map(|child| -negamax(child, depth - 1))
+            .max()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_pvs_matches_negamax() {
+        for position in XOT_POSITIONS.iter().take(5) {
+            for depth in 1..=4 {
+                let hash_table = HashTable::new(16);
+                let mut search = MidgameSearch::new(*position, &hash_table);
+
+                let limits = SearchLimits {
+                    depth: Some(depth),
+                    selectivity: Some(Selectivity::Level100),
+                    ..Default::default()
+                };
+                let result = search.search(&limits, &mut |_: SearchEvent| {});
+
+                assert_eq!(result.score as i32, negamax(position, depth));
+                assert_eq!(result.depth, depth);
+
+                // The best move has an exact score equal to the result
+                let best = result
+                    .root_moves
+                    .iter()
+                    .find(|m| m.move_ == result.best_move)
+                    .unwrap();
+                assert!(!best.is_upper_bound);
+                assert_eq!(best.score, result.score);
+            }
+        }
+    }
+
+    #[test]
+    fn test_selective_search() {
+        let mut nodes = vec![];
+
+        for selectivity in [Selectivity::Level73, Selectivity::Level100] {
+            let mut total_nodes = 0;
+
+            // ProbCut doesn't prune fewer nodes on every position, but does on a set
+            for position in XOT_POSITIONS.iter().take(8) {
+                let hash_table = HashTable::new(16);
+                let mut search = MidgameSearch::new(*position, &hash_table);
+
+                let limits = SearchLimits {
+                    depth: Some(7),
+                    selectivity: Some(selectivity),
+                    ..Default::default()
+                };
+                let result = search.search(&limits, &mut |_: SearchEvent| {});
+
+                assert!(position.is_valid_move(result.best_move));
+                assert_eq!(result.selectivity, selectivity);
+                total_nodes += result.nodes;
+            }
+            nodes.push(total_nodes);
+        }
+
+        // Pruning with ProbCut searches fewer nodes
+        assert!(nodes[0] < nodes[1], "{:?}", nodes);
+    }
+
     #[test]
     fn test_heuristic_bounds() {
         let position = Position::new();
-        let search = MidgameSearch::new(position);
+        let hash_table = HashTable::new(10);
+        let search = MidgameSearch::new(position, &hash_table);
         let score = search.heuristic();
 
         assert!(score > SCORE_MIN);
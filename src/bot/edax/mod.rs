@@ -1,4 +1,6 @@
 pub mod bot;
+pub mod endgame;
 pub mod eval;
 pub mod midgame;
+pub mod train;
 pub mod weights;
@@ -0,0 +1,406 @@
+use std::fmt;
+use std::io;
+
+use crate::bot::edax::endgame::EndgameSearch;
+use crate::bot::hash_table::HashTable;
+use crate::bot::{SearchEvent, SearchLimits};
+use crate::othello::position::Position;
+
+use super::eval::{Eval, EVAL_N_FEATURES};
+use super::weights::{
+    packed_indices, unpack_weights, EvalFile, EvalHeader, EVAL_N_PLY, EVAL_PACKED_SIZE,
+};
+
+/// A position with the score it should evaluate to, in discs for the player to move
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Sample {
+    pub position: Position,
+    pub score: i32,
+}
+
+/// Writes a line read by `parse_samples`: the board with the player to move as `X`,
+/// then the score
+impl fmt::Display for Sample {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.position.to_board_string(), self.score)
+    }
+}
+
+/// Settings for fitting the evaluation weights
+#[derive(Clone, Copy, Debug)]
+pub struct TrainOptions {
+    /// Passes of gradient descent over the training positions of each ply
+    pub epochs: usize,
+
+    /// Fraction of the mean error of the positions using a weight that is corrected
+    /// on each pass. Higher rates converge faster, but diverge above about 2 / 47
+    /// as every position has 47 features.
+    pub learning_rate: f32,
+
+    /// Percentage of the positions kept out of training to measure the validation error
+    pub validation_percent: u32,
+}
+
+impl Default for TrainOptions {
+    fn default() -> Self {
+        Self {
+            epochs: 100,
+            learning_rate: 0.02,
+            validation_percent: 10,
+        }
+    }
+}
+
+/// Root mean square errors of the fitted weights of a ply, in discs
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PlyError {
+    pub ply: usize,
+    pub n_train: usize,
+    pub n_validation: usize,
+
+    /// None if there are no positions of this ply in the set
+    pub train_error: Option<f64>,
+    pub validation_error: Option<f64>,
+}
+
+/// Parses samples with one position and its score per line, where positions are
+/// boards or transcripts as read by `Position::from_str`. Empty lines and lines
+/// starting with `#` are skipped.
+pub fn parse_samples(text: &str) -> io::Result<Vec<Sample>> {
+    let mut samples = Vec::new();
+
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let error = |message: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Line {}: {}", line_number + 1, message),
+            )
+        };
+
+        let (position, score) = line
+            .rsplit_once(char::is_whitespace)
+            .ok_or_else(|| error("Expected a position and a score".into()))?;
+        let score = score
+            .parse()
+            .map_err(|_| error(format!("Invalid score {:?}", score)))?;
+
+        let position: Position = position.parse().map_err(error)?;
+        if position.count_empty() > 60 {
+            return Err(error("Positions have at least 4 discs".into()));
+        }
+
+        samples.push(Sample { position, score });
+    }
+
+    Ok(samples)
+}
+
+/// Returns the positions where a move was played in a game from `start`, scored with
+/// the final disc difference for the player to move
+pub fn game_samples(start: &Position, moves: &[usize]) -> Vec<Sample> {
+    let mut position = *start;
+    let mut turn = 0;
+    let mut positions = Vec::new();
+
+    for &move_ in moves {
+        if !position.has_moves() {
+            position.pass();
+            turn = 1 - turn;
+        }
+
+        positions.push((position, turn));
+        position.do_move(move_);
+        turn = 1 - turn;
+    }
+
+    let score = position.final_score() as i32;
+    positions
+        .into_iter()
+        .map(|(position, player)| Sample {
+            position,
+            score: if player == turn { score } else { -score },
+        })
+        .collect()
+}
+
+/// Returns the exact score of `position` for the player to move. Entries of earlier
+/// calls stay in `hash_table`, they are exact scores as well.
+pub fn exact_score(position: &Position, hash_table: &HashTable) -> i32 {
+    if !position.has_moves() {
+        let mut passed = *position;
+        passed.pass();
+
+        if !passed.has_moves() {
+            return position.final_score() as i32;
+        }
+        return -exact_score(&passed, hash_table);
+    }
+
+    let mut search = EndgameSearch::new(*position, hash_table);
+    search
+        .search(&SearchLimits::default(), &mut |_: SearchEvent| {})
+        .score as i32
+}
+
+/// Returns true if `position` is kept out of training. The split depends on the
+/// position only, so duplicates of a validation position are not trained on.
+fn is_validation(position: &Position, validation_percent: u32) -> bool {
+    position.hash() % 100 < validation_percent as u64
+}
+
+/// Features of a position as indices among the packed weights of its ply
+struct PackedSample {
+    features: [u32; EVAL_N_FEATURES],
+    target: f32,
+}
+
+/// Returns the evaluation of a sample in 128ths of a disc, before rounding as `Eval::score`
+fn predict(weights: &[f32], sample: &PackedSample) -> f32 {
+    sample
+        .features
+        .iter()
+        .map(|&feature| weights[feature as usize])
+        .sum()
+}
+
+/// Returns the root mean square error in discs of `samples`, None if there are none
+fn rms_error(weights: &[f32], samples: &[PackedSample]) -> Option<f64> {
+    if samples.is_empty() {
+        return None;
+    }
+
+    let sum: f64 = samples
+        .iter()
+        .map(|sample| ((sample.target - predict(weights, sample)) as f64 / 128.0).powi(2))
+        .sum();
+    Some((sum / samples.len() as f64).sqrt())
+}
+
+/// Fits the weights of one ply by gradient descent on the squared error, moving each
+/// weight by the mean error of the positions using it
+fn fit_ply(weights: &mut [f32], samples: &[PackedSample], options: &TrainOptions) {
+    let mut counts = vec![0u32; weights.len()];
+    for sample in samples {
+        for &feature in &sample.features {
+            counts[feature as usize] += 1;
+        }
+    }
+    let used: Vec<usize> = (0..weights.len()).filter(|&k| counts[k] > 0).collect();
+
+    let mut gradient = vec![0f32; weights.len()];
+    for _ in 0..options.epochs {
+        for sample in samples {
+            let error = sample.target - predict(weights, sample);
+            for &feature in &sample.features {
+                gradient[feature as usize] += error;
+            }
+        }
+
+        for &k in &used {
+            weights[k] += options.learning_rate * gradient[k] / counts[k] as f32;
+            gradient[k] = 0.0;
+        }
+    }
+}
+
+/// Fits the weights of each ply to the scores of the samples of that ply and returns
+/// them as an evaluation file with the errors of each ply.
+///
+/// The weights start from `initial`, or zero, and keep their initial values on plies
+/// without training positions. Weights are fitted by symmetry class, so symmetric
+/// patterns share a weight as in `read_eval`.
+pub fn train(
+    samples: &[Sample],
+    initial: Option<&EvalFile>,
+    options: &TrainOptions,
+) -> (EvalFile, Vec<PlyError>) {
+    let packed_indices = packed_indices();
+    let n_w: usize = EVAL_PACKED_SIZE.iter().sum();
+
+    // Split the samples by ply and set, with the features of the player to move
+    let mut train_sets: Vec<Vec<PackedSample>> = (0..EVAL_N_PLY).map(|_| Vec::new()).collect();
+    let mut validation_sets: Vec<Vec<PackedSample>> = (0..EVAL_N_PLY).map(|_| Vec::new()).collect();
+
+    for sample in samples {
+        // Positions with fewer than 4 discs have no ply
+        let Some(ply) = 60usize.checked_sub(sample.position.count_empty() as usize) else {
+            continue;
+        };
+
+        let eval = Eval::new(&sample.position);
+        let packed = PackedSample {
+            features: eval
+                .features
+                .map(|feature| packed_indices[feature as usize] as u32),
+            target: (sample.score * 128) as f32,
+        };
+
+        if is_validation(&sample.position, options.validation_percent) {
+            validation_sets[ply].push(packed);
+        } else {
+            train_sets[ply].push(packed);
+        }
+    }
+
+    let mut packed_weights = Vec::with_capacity(EVAL_N_PLY);
+    let mut errors = Vec::with_capacity(EVAL_N_PLY);
+
+    for ply in 0..EVAL_N_PLY {
+        let mut weights = vec![0f32; n_w];
+        if let Some(initial) = initial {
+            for (j, &w) in initial.weights[0][ply].iter().enumerate() {
+                weights[packed_indices[j]] = w as f32;
+            }
+        }
+
+        fit_ply(&mut weights, &train_sets[ply], options);
+
+        // Errors are measured with the weights as they are saved
+        let rounded: Vec<i16> = weights
+            .iter()
+            .map(|&w| w.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16)
+            .collect();
+        let saved: Vec<f32> = rounded.iter().map(|&w| w as f32).collect();
+
+        errors.push(PlyError {
+            ply,
+            n_train: train_sets[ply].len(),
+            n_validation: validation_sets[ply].len(),
+            train_error: rms_error(&saved, &train_sets[ply]),
+            validation_error: rms_error(&saved, &validation_sets[ply]),
+        });
+        packed_weights.push(rounded);
+    }
+
+    let header = initial.map(|eval| eval.header).unwrap_or(EvalHeader {
+        version: 4,
+        release: 4,
+        build: 0,
+        date: 0.0,
+        big_endian: false,
+    });
+    let weights = (0..2)
+        .map(|player| {
+            packed_weights
+                .iter()
+                .map(|packed| unpack_weights(packed, player))
+                .collect()
+        })
+        .collect();
+
+    (EvalFile { header, weights }, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_samples() {
+        let text = "# comment\n\nf5d6 -2\n---------------------------OX------XO--------------------------- X 0\n";
+        let samples = parse_samples(text).unwrap();
+
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].score, -2);
+        assert_eq!(samples[1].position, Position::new());
+
+        let line = samples[0].to_string();
+        assert_eq!(parse_samples(&line).unwrap(), vec![samples[0]]);
+
+        let error = parse_samples("f5d6\nf5 x").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().starts_with("Line 1"));
+
+        let empty = format!("{} X 0", "-".repeat(64));
+        let error = parse_samples(&empty).unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("Positions have at least 4 discs"));
+    }
+
+    #[test]
+    fn test_game_samples() {
+        // Play the first valid move until the end of the game
+        let mut position = Position::new();
+        let mut moves = Vec::new();
+        let mut expected = Vec::new();
+        let mut sign = 1;
+        loop {
+            if !position.has_moves() {
+                position.pass();
+                sign = -sign;
+                if !position.has_moves() {
+                    break;
+                }
+            }
+            expected.push((position, sign));
+            let move_ = position.get_moves().trailing_zeros() as usize;
+            position.do_move(move_);
+            moves.push(move_);
+            sign = -sign;
+        }
+
+        // The final score is for the player to move at the end
+        let score = sign * position.final_score() as i32;
+        let samples = game_samples(&Position::new(), &moves);
+        assert_eq!(samples.len(), moves.len());
+        for (sample, (position, sign)) in samples.iter().zip(expected) {
+            assert_eq!(sample.position, position);
+            assert_eq!(sample.score, sign * score);
+        }
+    }
+
+    #[test]
+    fn test_exact_score() {
+        let hash_table = HashTable::new(10);
+        let sample = Sample {
+            position: Position::new_from_bitboards(!0 >> 1, 0),
+            score: 64,
+        };
+        assert_eq!(exact_score(&sample.position, &hash_table), 64);
+    }
+
+    #[test]
+    fn test_train() {
+        // Positions after one move all evaluate to their target, which is learnable
+        // as the four openings are symmetric to each other
+        let samples: Vec<Sample> = ["f5", "d3", "c4", "e6"]
+            .iter()
+            .map(|transcript| Sample {
+                position: transcript.parse().unwrap(),
+                score: -3,
+            })
+            .collect();
+        let options = TrainOptions {
+            validation_percent: 0,
+            ..Default::default()
+        };
+
+        let (eval, errors) = train(&samples, None, &options);
+        assert_eq!(errors.len(), EVAL_N_PLY);
+        assert_eq!(errors[1].n_train, 4);
+        assert!(errors[1].train_error.unwrap() < 0.1);
+        assert_eq!(errors[2].train_error, None);
+
+        let weights = &eval.weights[0][1];
+        for sample in &samples {
+            let features = Eval::new(&sample.position).features;
+            let score: i32 = features.iter().map(|&f| weights[f as usize] as i32).sum();
+            assert!((score - sample.score * 128).abs() < 128);
+        }
+        assert!(eval.weights[0][2].iter().all(|&w| w == 0));
+
+        let all_validation = TrainOptions {
+            validation_percent: 100,
+            ..Default::default()
+        };
+        let (_, errors) = train(&samples, None, &all_validation);
+        assert_eq!(errors[1].n_validation, 4);
+        assert_eq!(errors[1].validation_error, Some(3.0));
+    }
+}
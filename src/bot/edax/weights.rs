@@ -1,5 +1,8 @@
 use lazy_static::lazy_static;
-use std::io::Read;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 const EDAX: i32 = 0x58414445; // "EDAX" in ASCII/hex
 const XADE: i32 = 0x45444158; // "XADE" in ASCII/hex (byte-swapped EDAX)
@@ -7,10 +10,10 @@ const EVAL: i32 = 0x4C415645; // "EVAL" in ASCII/hex
 const LAVE: i32 = 0x4556414C; // "LAVE" in ASCII/hex (byte-swapped EVAL)
 
 /** number of (unpacked) weights */
-const EVAL_N_WEIGHT: usize = 226315;
+pub const EVAL_N_WEIGHT: usize = 226315;
 
 /** number of plies */
-const EVAL_N_PLY: usize = 61;
+pub const EVAL_N_PLY: usize = 61;
 
 /** feature size */
 pub const EVAL_SIZE: [usize; 13] = [
@@ -235,152 +238,296 @@ lazy_static! {
 }
 
 lazy_static! {
-    pub static ref EVAL_WEIGHT: Vec<Vec<Vec<i16>>> = load_eval().unwrap();
+    /// Weights of the evaluation file by player, ply and feature. Panics if the file
+    /// can't be loaded, callers that may run without it check `load_weights` first.
+    pub static ref EVAL_WEIGHT: &'static [Vec<Vec<i16>>] =
+        load_weights().unwrap_or_else(|error| panic!("{}", error));
 }
 
-pub fn load_eval() -> Result<Vec<Vec<Vec<i16>>>, std::io::Error> {
-    let mut file = std::fs::File::open("eval.dat")?;
+/// Evaluation file loaded when no other one is set with `set_eval_path` or `EVAL_PATH_VAR`
+pub const DEFAULT_EVAL_PATH: &str = "eval.dat";
 
+/// Environment variable with the path of the evaluation file
+pub const EVAL_PATH_VAR: &str = "SWAP_EVAL";
+
+static EVAL_PATH: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+static LOADED_WEIGHTS: OnceLock<Result<Vec<Vec<Vec<i16>>>, String>> = OnceLock::new();
+
+/// Sets the evaluation file `EVAL_WEIGHT` is loaded from, instead of the one of
+/// `EVAL_PATH_VAR`. Fails once the weights are loaded or the path was already set.
+pub fn set_eval_path(path: PathBuf) -> Result<(), String> {
+    EVAL_PATH
+        .set(Some(path))
+        .map_err(|_| "The evaluation file is already chosen".to_string())
+}
+
+/// Returns the evaluation file chosen with `set_eval_path` or `EVAL_PATH_VAR`, if any
+fn chosen_eval_path() -> Option<&'static Path> {
+    EVAL_PATH
+        .get_or_init(|| std::env::var_os(EVAL_PATH_VAR).map(PathBuf::from))
+        .as_deref()
+}
+
+/// Loads the evaluation file on the first call and returns the same result on later
+/// ones. With the `embedded-data` feature, the weights compiled into the binary are
+/// used unless a file is chosen. The edax bot is unavailable if this fails.
+pub fn load_weights() -> Result<&'static [Vec<Vec<i16>>], String> {
+    LOADED_WEIGHTS
+        .get_or_init(|| {
+            #[cfg(feature = "embedded-data")]
+            if chosen_eval_path().is_none() {
+                return read_eval(&mut crate::embedded::eval().as_slice())
+                    .map(|eval| eval.weights)
+                    .map_err(|error| {
+                        format!("Cannot load the embedded evaluation weights: {}", error)
+                    });
+            }
+
+            let path = chosen_eval_path().unwrap_or(Path::new(DEFAULT_EVAL_PATH));
+            load_eval(path).map(|eval| eval.weights).map_err(|error| {
+                format!(
+                    "Cannot load the evaluation weights from {}: {}",
+                    path.display(),
+                    error
+                )
+            })
+        })
+        .as_deref()
+        .map_err(Clone::clone)
+}
+
+/// Header of an evaluation file of Edax
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct EvalHeader {
+    pub version: i32,
+    pub release: i32,
+    pub build: i32,
+    /// Date the weights were computed, stored by Edax as a double
+    pub date: f64,
+    /// Byte order of everything after the magic numbers. Edax writes files in the
+    /// order of the machine, so usually little-endian.
+    pub big_endian: bool,
+}
+
+/// Evaluation file of Edax with its header and unpacked weights
+#[derive(Clone, PartialEq, Debug)]
+pub struct EvalFile {
+    pub header: EvalHeader,
+    /// Weights by player, ply and feature, as in `EVAL_WEIGHT`
+    pub weights: Vec<Vec<Vec<i16>>>,
+}
+
+/// Reads the evaluation file at `path`, see `read_eval`
+pub fn load_eval(path: &Path) -> Result<EvalFile, std::io::Error> {
+    read_eval(&mut BufReader::new(File::open(path)?))
+}
+
+/// Reads an evaluation file of Edax, see `EvalFile`
+pub fn read_eval(reader: &mut impl Read) -> Result<EvalFile, std::io::Error> {
     // Read headers
     let mut edax_header = [0u8; 4];
     let mut eval_header = [0u8; 4];
-    file.read_exact(&mut edax_header)?;
-    file.read_exact(&mut eval_header)?;
+    reader.read_exact(&mut edax_header)?;
+    reader.read_exact(&mut eval_header)?;
 
     let edax_header = i32::from_le_bytes(edax_header);
     let eval_header = i32::from_le_bytes(eval_header);
 
-    // Validate headers
-    if !((edax_header == EDAX && eval_header == EVAL)
-        || (edax_header == XADE && eval_header == LAVE))
-    {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            "Not an Edax evaluation file",
-        ));
-    }
+    // Validate headers, the magic numbers give the byte order of the rest of the file
+    let big_endian = match (edax_header, eval_header) {
+        (EDAX, EVAL) => true,
+        (XADE, LAVE) => false,
+        _ => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Not an Edax evaluation file",
+            ))
+        }
+    };
 
     // Read version info
-    let mut version_bytes = [0u8; 4];
-    let mut release_bytes = [0u8; 4];
-    let mut build_bytes = [0u8; 4];
-    let mut date_bytes = [0u8; 8];
+    let mut int_bytes = [0u8; 4];
+    let mut read_i32 = |reader: &mut dyn Read| -> Result<i32, std::io::Error> {
+        reader.read_exact(&mut int_bytes)?;
+        Ok(if big_endian {
+            i32::from_be_bytes(int_bytes)
+        } else {
+            i32::from_le_bytes(int_bytes)
+        })
+    };
+    let version = read_i32(reader)?;
+    let release = read_i32(reader)?;
+    let build = read_i32(reader)?;
 
-    file.read_exact(&mut version_bytes)?;
-    file.read_exact(&mut release_bytes)?;
-    file.read_exact(&mut build_bytes)?;
-    file.read_exact(&mut date_bytes)?;
+    let mut date_bytes = [0u8; 8];
+    reader.read_exact(&mut date_bytes)?;
+    let date = if big_endian {
+        f64::from_be_bytes(date_bytes)
+    } else {
+        f64::from_le_bytes(date_bytes)
+    };
 
-    let mut _version = i32::from_be_bytes(version_bytes);
-    let mut _release = i32::from_be_bytes(release_bytes);
-    let mut _build = i32::from_be_bytes(build_bytes);
+    // Read the packed weights of each ply and unpack them for both players
+    let n_w = EVAL_PACKED_SIZE.iter().sum::<usize>();
+    let mut bytes = vec![0u8; n_w * 2];
+    let mut weights = vec![Vec::new(), Vec::new()];
+    for _ in 0..EVAL_N_PLY {
+        reader.read_exact(&mut bytes)?;
+        let packed: Vec<i16> = bytes
+            .chunks_exact(2)
+            .map(|pair| {
+                if big_endian {
+                    i16::from_be_bytes([pair[0], pair[1]])
+                } else {
+                    i16::from_le_bytes([pair[0], pair[1]])
+                }
+            })
+            .collect();
 
-    // Byte swap if needed
-    if edax_header == XADE {
-        _version = _version.swap_bytes();
-        _release = _release.swap_bytes();
-        _build = _build.swap_bytes();
+        for (player, player_weights) in weights.iter_mut().enumerate() {
+            player_weights.push(unpack_weights(&packed, player));
+        }
     }
 
-    // Create buffer for weights
-    let n_w = EVAL_PACKED_SIZE.iter().sum();
-    let mut w = vec![0i16; n_w];
+    Ok(EvalFile {
+        header: EvalHeader {
+            version,
+            release,
+            build,
+            date,
+            big_endian,
+        },
+        weights,
+    })
+}
 
-    // Create 3D array for weights directly on heap
-    let mut eval_weight = vec![vec![vec![0i16; EVAL_N_WEIGHT]; EVAL_N_PLY]; 2];
+/// Writes the evaluation file at `path`, see `write_eval`
+pub fn save_eval(path: &Path, eval: &EvalFile) -> Result<(), std::io::Error> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_eval(&mut writer, eval)?;
+    writer.flush()
+}
 
-    // Load weights for each ply
-    for ply in 0..EVAL_N_PLY {
-        // Read weights
-        let mut bytes = vec![0u8; n_w * 2];
-        file.read_exact(&mut bytes)?;
+/// Writes an evaluation file of Edax in the byte order of its header. The weights of
+/// the first player are packed by feature symmetry, so those of symmetric
+/// configurations must be equal, as in any file read by `read_eval`.
+pub fn write_eval(writer: &mut impl Write, eval: &EvalFile) -> Result<(), std::io::Error> {
+    let header = &eval.header;
+    if eval.weights.len() != 2
+        || eval.weights.iter().any(|player_weights| {
+            player_weights.len() != EVAL_N_PLY
+                || player_weights.iter().any(|w| w.len() != EVAL_N_WEIGHT)
+        })
+    {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Evaluation weights must be given for 2 players and 61 plies",
+        ));
+    }
 
-        // Convert bytes to i16 weights
-        for i in 0..n_w {
-            let val = i16::from_be_bytes([bytes[i * 2], bytes[i * 2 + 1]]);
-            w[i] = if edax_header == XADE {
-                val.swap_bytes()
-            } else {
-                val
-            };
+    // The magic numbers are read little-endian, their order tells the reader the byte order
+    let (edax_header, eval_header) = if header.big_endian {
+        (EDAX, EVAL)
+    } else {
+        (XADE, LAVE)
+    };
+    writer.write_all(&edax_header.to_le_bytes())?;
+    writer.write_all(&eval_header.to_le_bytes())?;
+
+    for value in [header.version, header.release, header.build] {
+        if header.big_endian {
+            writer.write_all(&value.to_be_bytes())?;
+        } else {
+            writer.write_all(&value.to_le_bytes())?;
         }
+    }
+    if header.big_endian {
+        writer.write_all(&header.date.to_be_bytes())?;
+    } else {
+        writer.write_all(&header.date.to_le_bytes())?;
+    }
 
-        let mut j = 0;
-        let mut offset = 0;
-
-        // Process each feature type
-        for i in 0..13 {
-            let size = EVAL_SIZE[i];
-
-            // Map weights using the appropriate eval array
-            match i {
-                0 => {
-                    for k in 0..size {
-                        eval_weight[0][ply][j] = w[EVAL_C9[0][k] + offset];
-                        eval_weight[1][ply][j] = w[EVAL_C9[1][k] + offset];
-                        j += 1;
-                    }
-                }
-                1 => {
-                    for k in 0..size {
-                        eval_weight[0][ply][j] = w[EVAL_C10[0][k] + offset];
-                        eval_weight[1][ply][j] = w[EVAL_C10[1][k] + offset];
-                        j += 1;
-                    }
-                }
-                2 | 3 => {
-                    for k in 0..size {
-                        eval_weight[0][ply][j] = w[EVAL_S10[0][k] + offset];
-                        eval_weight[1][ply][j] = w[EVAL_S10[1][k] + offset];
-                        j += 1;
-                    }
-                }
-                4..=7 => {
-                    for k in 0..size {
-                        eval_weight[0][ply][j] = w[EVAL_S8[0][k] + offset];
-                        eval_weight[1][ply][j] = w[EVAL_S8[1][k] + offset];
-                        j += 1;
-                    }
-                }
-                8 => {
-                    for k in 0..size {
-                        eval_weight[0][ply][j] = w[EVAL_S7[0][k] + offset];
-                        eval_weight[1][ply][j] = w[EVAL_S7[1][k] + offset];
-                        j += 1;
-                    }
-                }
-                9 => {
-                    for k in 0..size {
-                        eval_weight[0][ply][j] = w[EVAL_S6[0][k] + offset];
-                        eval_weight[1][ply][j] = w[EVAL_S6[1][k] + offset];
-                        j += 1;
-                    }
+    for ply_weights in &eval.weights[0] {
+        let bytes: Vec<u8> = pack_weights(ply_weights)
+            .into_iter()
+            .flat_map(|w| {
+                if header.big_endian {
+                    w.to_be_bytes()
+                } else {
+                    w.to_le_bytes()
                 }
-                10 => {
-                    for k in 0..size {
-                        eval_weight[0][ply][j] = w[EVAL_S5[0][k] + offset];
-                        eval_weight[1][ply][j] = w[EVAL_S5[1][k] + offset];
-                        j += 1;
-                    }
-                }
-                11 => {
-                    for k in 0..size {
-                        eval_weight[0][ply][j] = w[EVAL_S4[0][k] + offset];
-                        eval_weight[1][ply][j] = w[EVAL_S4[1][k] + offset];
-                        j += 1;
-                    }
-                }
-                12 => {
-                    eval_weight[0][ply][j] = w[offset];
-                    eval_weight[1][ply][j] = w[offset];
-                }
-                _ => unreachable!(),
+            })
+            .collect();
+        writer.write_all(&bytes)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the symmetry packing of each feature: the index among the packed weights of
+/// the feature for each player and configuration, or None for the constant feature
+fn feature_packing(feature: usize) -> Option<&'static [Vec<usize>]> {
+    match feature {
+        0 => Some(&EVAL_C9),
+        1 => Some(&EVAL_C10),
+        2 | 3 => Some(&EVAL_S10),
+        4..=7 => Some(&EVAL_S8),
+        8 => Some(&EVAL_S7),
+        9 => Some(&EVAL_S6),
+        10 => Some(&EVAL_S5),
+        11 => Some(&EVAL_S4),
+        12 => None,
+        _ => unreachable!(),
+    }
+}
+
+/// Returns the weights of a ply for `player` from its packed weights
+pub fn unpack_weights(packed: &[i16], player: usize) -> Vec<i16> {
+    let mut weights = Vec::with_capacity(EVAL_N_WEIGHT);
+    let mut offset = 0;
+
+    for feature in 0..EVAL_SIZE.len() {
+        match feature_packing(feature) {
+            Some(packing) => weights.extend(
+                packing[player][..EVAL_SIZE[feature]]
+                    .iter()
+                    .map(|&k| packed[k + offset]),
+            ),
+            None => weights.push(packed[offset]),
+        }
+        offset += EVAL_PACKED_SIZE[feature];
+    }
+
+    weights
+}
+
+/// Returns the index among the packed weights of a ply of each weight of the first player
+pub fn packed_indices() -> Vec<usize> {
+    let mut indices = Vec::with_capacity(EVAL_N_WEIGHT);
+    let mut offset = 0;
+
+    for feature in 0..EVAL_SIZE.len() {
+        match feature_packing(feature) {
+            Some(packing) => {
+                indices.extend(packing[0][..EVAL_SIZE[feature]].iter().map(|&k| k + offset))
             }
-            offset += EVAL_PACKED_SIZE[i];
+            None => indices.push(offset),
         }
+        offset += EVAL_PACKED_SIZE[feature];
     }
 
-    Ok(eval_weight)
+    indices
+}
+
+/// Returns the packed weights of a ply from the weights of the first player, the
+/// inverse of `unpack_weights`
+pub fn pack_weights(weights: &[i16]) -> Vec<i16> {
+    let mut packed = vec![0i16; EVAL_PACKED_SIZE.iter().sum()];
+    for (&index, &w) in packed_indices().iter().zip(weights) {
+        packed[index] = w;
+    }
+    packed
 }
 
 fn opponent_feature(feature: usize, feature_size: usize) -> usize {
@@ -423,4 +570,71 @@ mod tests {
         assert_eq!(&EVAL_WEIGHT[0][60][0..10], &ply60_p0);
         assert_eq!(&EVAL_WEIGHT[1][60][0..10], &ply60_p1);
     }
+
+    #[test]
+    fn test_load_eval_errors() {
+        let missing = load_eval(Path::new("missing.dat")).unwrap_err();
+        assert_eq!(missing.kind(), std::io::ErrorKind::NotFound);
+
+        let not_eval = load_eval(Path::new("Cargo.toml")).unwrap_err();
+        assert_eq!(not_eval.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_eval_round_trip() {
+        let bytes = std::fs::read(DEFAULT_EVAL_PATH).unwrap();
+        let eval = read_eval(&mut bytes.as_slice()).unwrap();
+
+        let mut written = Vec::new();
+        write_eval(&mut written, &eval).unwrap();
+        assert!(written == bytes);
+    }
+
+    #[test]
+    fn test_write_eval_byte_orders() {
+        let n_w: usize = EVAL_PACKED_SIZE.iter().sum();
+        let packed: Vec<Vec<i16>> = (0..EVAL_N_PLY)
+            .map(|ply| {
+                (0..n_w)
+                    .map(|i| ((i * 7919 + ply * 104729) % 65536) as u16 as i16)
+                    .collect()
+            })
+            .collect();
+        for ply_packed in &packed {
+            assert_eq!(&pack_weights(&unpack_weights(ply_packed, 0)), ply_packed);
+        }
+
+        let weights = (0..2)
+            .map(|player| {
+                packed
+                    .iter()
+                    .map(|ply_packed| unpack_weights(ply_packed, player))
+                    .collect()
+            })
+            .collect();
+        let mut eval = EvalFile {
+            header: EvalHeader {
+                version: 4,
+                release: 4,
+                build: 1234,
+                date: 1.5e9,
+                big_endian: false,
+            },
+            weights,
+        };
+
+        for (big_endian, magic) in [(false, b"XADELAVE"), (true, b"EDAXEVAL")] {
+            eval.header.big_endian = big_endian;
+            let mut written = Vec::new();
+            write_eval(&mut written, &eval).unwrap();
+
+            assert_eq!(&written[..8], magic);
+            assert_eq!(written.len(), 28 + EVAL_N_PLY * n_w * 2);
+            assert!(read_eval(&mut written.as_slice()).unwrap() == eval);
+        }
+
+        eval.weights[1].pop();
+        let invalid = write_eval(&mut Vec::new(), &eval).unwrap_err();
+        assert_eq!(invalid.kind(), std::io::ErrorKind::InvalidInput);
+    }
 }
@@ -0,0 +1,461 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::bot::Selectivity;
+use crate::othello::{position::Position, squares::PASS};
+
+/// Value of `best_move` when an entry has no known best move
+pub const NO_MOVE: u8 = 65;
+
+/// Default size of a bot's hash table, as number of bits of the entry index
+pub const DEFAULT_HASH_TABLE_BITS: u32 = 20;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct HashEntry {
+    /// Player bitboard of the stored position
+    pub player: u64,
+
+    /// Opponent bitboard of the stored position
+    pub opponent: u64,
+
+    /// Search depth that produced the bounds
+    pub depth: u32,
+
+    /// Selectivity of the search that produced the bounds
+    pub selectivity: Selectivity,
+
+    /// Lower bound of the score
+    pub lower: i32,
+
+    /// Upper bound of the score
+    pub upper: i32,
+
+    /// Best move found, or `NO_MOVE`
+    pub best_move: u8,
+
+    /// Generation of the search that stored this entry
+    pub generation: u8,
+
+    /// True if the bounds were solved to the end of the game by the endgame search,
+    /// while midgame searches, even deep enough to reach it, rely on the evaluation
+    pub is_endgame: bool,
+}
+
+impl Default for HashEntry {
+    fn default() -> Self {
+        Self {
+            player: 0,
+            opponent: 0,
+            depth: 0,
+            selectivity: Selectivity::Level100,
+            lower: i32::MIN,
+            upper: i32::MAX,
+            best_move: NO_MOVE,
+            generation: 0,
+            is_endgame: false,
+        }
+    }
+}
+
+impl HashEntry {
+    fn matches(&self, position: &Position) -> bool {
+        self.player == position.player && self.opponent == position.opponent
+    }
+
+    /// Returns true if the bounds are at least as reliable as a search with
+    /// `depth` and `selectivity` would give.
+    pub fn covers(&self, depth: u32, selectivity: Selectivity) -> bool {
+        self.depth >= depth && self.selectivity >= selectivity
+    }
+
+    /// Returns true if the bounds were solved by an endgame search at least as
+    /// reliable as one with `selectivity` would be
+    pub fn covers_endgame(&self, selectivity: Selectivity) -> bool {
+        self.is_endgame && self.selectivity >= selectivity
+    }
+}
+
+/// Fixed-size transposition table shared by the midgame and endgame searches.
+///
+/// Entries of older generations are always replaced. Within the same generation
+/// an entry is only replaced by a search of at least the same depth and selectivity,
+/// and endgame entries only by endgame searches.
+///
+/// Entries are locked individually, so threads of a parallel search can share the table.
+pub struct HashTable {
+    entries: Vec<Mutex<HashEntry>>,
+    mask: usize,
+    generation: u8,
+}
+
+impl HashTable {
+    pub fn new(bits: u32) -> Self {
+        let size = 1usize << bits;
+
+        Self {
+            entries: (0..size)
+                .map(|_| Mutex::new(HashEntry::default()))
+                .collect(),
+            mask: size - 1,
+            generation: 0,
+        }
+    }
+
+    /// Marks the start of a new search, so entries of earlier searches can be replaced.
+    /// Callers do this once per move, before starting any search threads.
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    pub fn clear(&mut self) {
+        for entry in &mut self.entries {
+            *entry.get_mut().unwrap_or_else(PoisonError::into_inner) = HashEntry::default();
+        }
+        self.generation = 0;
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn index(&self, position: &Position) -> usize {
+        position.hash() as usize & self.mask
+    }
+
+    /// Locks the entry where `position` is stored
+    fn entry(&self, position: &Position) -> MutexGuard<'_, HashEntry> {
+        // A thread panicking while holding the lock cannot leave the entry half-written
+        self.entries[self.index(position)]
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the entry of `position`, whatever its generation. Results of earlier
+    /// searches stay valid for the depth and selectivity they were searched at, the
+    /// generation only decides which entries `store` may replace.
+    pub fn get(&self, position: &Position) -> Option<HashEntry> {
+        let entry = self.entry(position);
+
+        if entry.matches(position) {
+            Some(*entry)
+        } else {
+            None
+        }
+    }
+
+    /// Stores the result of searching `position` with window (`alpha`, `beta`).
+    #[allow(clippy::too_many_arguments)]
+    pub fn store(
+        &self,
+        position: &Position,
+        depth: u32,
+        selectivity: Selectivity,
+        alpha: i32,
+        beta: i32,
+        score: i32,
+        best_move: usize,
+    ) {
+        let bounds = Self::bounds(alpha, beta, score);
+        self.store_entry(position, depth, selectivity, bounds, best_move, false);
+    }
+
+    /// Stores the result of solving `position` with window (`alpha`, `beta`), with
+    /// the number of empties as depth. Only these entries are used by `covers_endgame`.
+    pub fn store_endgame(
+        &self,
+        position: &Position,
+        selectivity: Selectivity,
+        alpha: i32,
+        beta: i32,
+        score: i32,
+        best_move: usize,
+    ) {
+        let bounds = Self::bounds(alpha, beta, score);
+        let depth = position.count_empty();
+        self.store_entry(position, depth, selectivity, bounds, best_move, true);
+    }
+
+    /// Returns the lower and upper bound given by a search result
+    fn bounds(alpha: i32, beta: i32, score: i32) -> (i32, i32) {
+        if score <= alpha {
+            (i32::MIN, score)
+        } else if score >= beta {
+            (score, i32::MAX)
+        } else {
+            (score, score)
+        }
+    }
+
+    fn store_entry(
+        &self,
+        position: &Position,
+        depth: u32,
+        selectivity: Selectivity,
+        (lower, upper): (i32, i32),
+        best_move: usize,
+        is_endgame: bool,
+    ) {
+        let generation = self.generation;
+        let mut entry = self.entry(position);
+
+        if entry.matches(position)
+            && entry.depth == depth
+            && entry.selectivity == selectivity
+            && entry.is_endgame == is_endgame
+        {
+            // Same search depth: tighten the bounds we already have. Selective searches
+            // and threads sharing the table can return bounds that contradict the stored
+            // ones, then the new result replaces them.
+            let (tight_lower, tight_upper) = (entry.lower.max(lower), entry.upper.min(upper));
+            if tight_lower <= tight_upper {
+                (entry.lower, entry.upper) = (tight_lower, tight_upper);
+            } else {
+                (entry.lower, entry.upper) = (lower, upper);
+            }
+            entry.best_move = best_move as u8;
+            entry.generation = generation;
+            return;
+        }
+
+        let priority = (is_endgame, depth, selectivity);
+        if entry.generation == generation
+            && (entry.is_endgame, entry.depth, entry.selectivity) > priority
+        {
+            // Keep deeper result of the current search, also when a shallow search such as
+            // a ProbCut or move ordering search visits the same position. Endgame results
+            // count as deeper than any midgame result, and at the same depth less selective
+            // results are kept.
+            return;
+        }
+
+        *entry = HashEntry {
+            player: position.player,
+            opponent: position.opponent,
+            depth,
+            selectivity,
+            lower,
+            upper,
+            best_move: best_move as u8,
+            generation,
+            is_endgame,
+        };
+    }
+
+    /// Follows the best moves stored in the table, starting with `first_move` in `position`.
+    /// The result has at most `max_len` moves, excluding passes, which are added as `PASS`.
+    pub fn principal_variation(
+        &self,
+        position: &Position,
+        first_move: usize,
+        max_len: usize,
+    ) -> Vec<usize> {
+        let mut pv = vec![first_move];
+        let mut position = position.do_move_cloned(first_move);
+
+        while pv.iter().filter(|&&move_| move_ != PASS).count() < max_len {
+            if !position.has_moves() {
+                if position.get_opponent_moves() == 0 {
+                    break;
+                }
+
+                position.pass();
+                pv.push(PASS);
+            }
+
+            let Some(entry) = self.get(&position) else {
+                break;
+            };
+
+            let move_ = entry.best_move as usize;
+            if !position.is_valid_move(move_) {
+                break;
+            }
+
+            pv.push(move_);
+            position.do_move(move_);
+        }
+
+        // Don't end the line with a pass
+        if pv.last() == Some(&PASS) {
+            pv.pop();
+        }
+
+        pv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_missing() {
+        let table = HashTable::new(4);
+        assert_eq!(table.len(), 16);
+        assert!(table.get(&Position::new()).is_none());
+    }
+
+    #[test]
+    fn test_store_bounds() {
+        let table = HashTable::new(4);
+        let position = Position::new();
+
+        // Fail low gives an upper bound
+        table.store(&position, 3, Selectivity::Level100, -10, 10, -20, 19);
+        let entry = table.get(&position).unwrap();
+        assert_eq!((entry.lower, entry.upper), (i32::MIN, -20));
+        assert_eq!(entry.best_move, 19);
+        assert_eq!(entry.depth, 3);
+
+        // Fail high at the same depth tightens the bounds
+        table.store(&position, 3, Selectivity::Level100, -30, -25, -22, 26);
+        let entry = table.get(&position).unwrap();
+        assert_eq!((entry.lower, entry.upper), (-22, -20));
+        assert_eq!(entry.best_move, 26);
+
+        // Contradicting bounds at the same depth replace the old ones
+        table.store(&position, 3, Selectivity::Level100, -10, 10, 0, 37);
+        let entry = table.get(&position).unwrap();
+        assert_eq!((entry.lower, entry.upper), (0, 0));
+        assert_eq!(entry.best_move, 37);
+
+        // Exact score at a different depth replaces the entry
+        table.store(&position, 4, Selectivity::Level100, -10, 10, 5, 37);
+        let entry = table.get(&position).unwrap();
+        assert_eq!((entry.lower, entry.upper), (5, 5));
+        assert_eq!(entry.depth, 4);
+    }
+
+    #[test]
+    fn test_store_selectivity() {
+        let table = HashTable::new(4);
+        let position = Position::new();
+
+        table.store(&position, 6, Selectivity::Level73, -10, 10, 3, 19);
+        let entry = table.get(&position).unwrap();
+        assert!(entry.covers(6, Selectivity::Level73));
+        assert!(entry.covers(5, Selectivity::Level73));
+        assert!(!entry.covers(7, Selectivity::Level73));
+        assert!(!entry.covers(6, Selectivity::Level100));
+
+        // Bounds of a search with other selectivity are replaced instead of tightened
+        table.store(&position, 6, Selectivity::Level100, -10, 10, -5, 26);
+        let entry = table.get(&position).unwrap();
+        assert_eq!((entry.lower, entry.upper), (-5, -5));
+        assert!(entry.covers(6, Selectivity::Level100));
+    }
+
+    #[test]
+    fn test_store_endgame() {
+        let table = HashTable::new(4);
+        let position = Position::new();
+
+        // A midgame search as deep as the number of empties is not an endgame result
+        table.store(&position, 60, Selectivity::Level100, -10, 10, 3, 19);
+        let entry = table.get(&position).unwrap();
+        assert!(entry.covers(60, Selectivity::Level100));
+        assert!(!entry.covers_endgame(Selectivity::Level73));
+
+        // Endgame results replace midgame ones and are used by both searches
+        table.store_endgame(&position, Selectivity::Level100, -10, 10, 5, 26);
+        let entry = table.get(&position).unwrap();
+        assert_eq!((entry.lower, entry.upper), (5, 5));
+        assert!(entry.covers_endgame(Selectivity::Level100));
+        assert!(entry.covers(60, Selectivity::Level100));
+
+        // Deeper midgame results of the same search don't replace them
+        table.store(&position, 61, Selectivity::Level100, -10, 10, 3, 19);
+        let entry = table.get(&position).unwrap();
+        assert!(entry.is_endgame);
+        assert_eq!(entry.best_move, 26);
+
+        // Nor do more selective endgame results
+        table.store_endgame(&position, Selectivity::Level73, -10, 10, 7, 37);
+        let entry = table.get(&position).unwrap();
+        assert!(entry.covers_endgame(Selectivity::Level100));
+        assert_eq!((entry.lower, entry.upper), (5, 5));
+    }
+
+    #[test]
+    fn test_replacement() {
+        // Table with a single entry, so every position collides
+        let mut table = HashTable::new(0);
+        let position = Position::new();
+        let other = position.do_move_cloned(19);
+
+        table.store(&position, 8, Selectivity::Level100, -64, 64, 0, 19);
+
+        // Shallower result of the same generation does not replace the deeper one
+        table.store(&other, 2, Selectivity::Level100, -64, 64, 0, 18);
+        assert!(table.get(&position).is_some());
+        assert!(table.get(&other).is_none());
+
+        // Also not if it is a result for the same position
+        table.store(&position, 2, Selectivity::Level100, -64, 64, 5, 26);
+        let entry = table.get(&position).unwrap();
+        assert_eq!((entry.depth, entry.best_move), (8, 19));
+
+        // Any result of a newer generation replaces it
+        table.new_search();
+        table.store(&other, 2, Selectivity::Level100, -64, 64, 0, 18);
+        assert!(table.get(&position).is_none());
+        assert!(table.get(&other).is_some());
+
+        table.clear();
+        assert!(table.get(&other).is_none());
+    }
+
+    #[test]
+    fn test_concurrent_store() {
+        // Table with a single entry, so all threads write the same entry
+        let table = HashTable::new(0);
+        let positions = Position::new().children();
+
+        std::thread::scope(|scope| {
+            for (index, position) in positions.iter().enumerate() {
+                let table = &table;
+                let positions = &positions;
+
+                scope.spawn(move || {
+                    for _ in 0..1000 {
+                        let score = index as i32;
+                        table.store(position, 1, Selectivity::Level100, -64, 64, score, index);
+
+                        // An entry never mixes the fields of two stores
+                        for (index, position) in positions.iter().enumerate() {
+                            if let Some(entry) = table.get(position) {
+                                assert_eq!(
+                                    (entry.lower, entry.upper),
+                                    (index as i32, index as i32)
+                                );
+                                assert_eq!(entry.best_move as usize, index);
+                            }
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    fn test_principal_variation() {
+        let table = HashTable::new(10);
+        let position = Position::new();
+
+        let child = position.do_move_cloned(19);
+        table.store(&child, 2, Selectivity::Level100, -64, 64, 0, 18);
+
+        let grandchild = child.do_move_cloned(18);
+        table.store(&grandchild, 1, Selectivity::Level100, -64, 64, 0, 17);
+
+        assert_eq!(
+            table.principal_variation(&position, 19, 10),
+            vec![19, 18, 17]
+        );
+        assert_eq!(table.principal_variation(&position, 19, 2), vec![19, 18]);
+        assert_eq!(table.principal_variation(&position, 26, 10), vec![26]);
+    }
+}
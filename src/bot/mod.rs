@@ -1,32 +1,419 @@
-use std::time::Duration;
+use std::cell::Cell;
+use std::fmt::{self, Display};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use book::bot::BookBot;
+use book::load_book;
 use edax::bot::EdaxBot;
+use edax::weights::load_weights;
 use random::RandomBot;
 use squared::bot::SquaredBot;
 
 use crate::othello::position::Position;
+use crate::othello::squares::square_name;
 
+pub mod book;
 pub mod edax;
+pub mod hash_table;
+pub mod move_ordering;
 pub mod random;
 pub mod squared;
 
+/// Player that chooses moves, created by name with `get_bot`
 pub trait Bot: Send {
-    // Returns the index of a valid move
-    fn get_move(&mut self, position: &Position) -> usize;
+    /// Searches the position, the best move of the result is a valid move.
+    /// The position must have a valid move.
+    fn search(
+        &mut self,
+        position: &Position,
+        limits: &SearchLimits,
+        observer: &mut dyn SearchObserver,
+    ) -> SearchResult;
+
+    /// Returns the index of a valid move
+    fn get_move(&mut self, position: &Position, limits: &SearchLimits) -> usize {
+        self.search(position, limits, &mut |_: SearchEvent| {})
+            .best_move
+    }
+}
+
+/// Progress reported by a running search
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SearchEvent<'a> {
+    /// A move at the root was searched completely at `depth`
+    RootMove {
+        depth: u32,
+        root_move: RootMoveScore,
+    },
+
+    /// An iteration completed, the result is what the search returns if stopped now
+    DepthCompleted(&'a SearchResult),
+}
+
+/// Receives progress of a running search, closures taking a `SearchEvent` implement it
+pub trait SearchObserver: Send {
+    fn notify(&mut self, event: SearchEvent);
+}
+
+impl<F: FnMut(SearchEvent) + Send> SearchObserver for F {
+    fn notify(&mut self, event: SearchEvent) {
+        self(event)
+    }
 }
 
+/// Limits on how much effort a bot may spend on finding a move.
+/// Unset fields are not limited, except for `depth` where the bot uses its default.
+#[derive(Clone, Default, Debug)]
+pub struct SearchLimits {
+    /// Wall-clock budget for the whole search
+    pub time: Option<Duration>,
+
+    /// Maximum midgame search depth
+    pub depth: Option<u32>,
+
+    /// Maximum number of nodes to search
+    pub nodes: Option<u64>,
+
+    /// Flag that stops the search when set, can be shared with other threads
+    pub stop: Option<Arc<AtomicBool>>,
+
+    /// Nodes of all threads of a parallel search. Each thread adds its nodes to it,
+    /// and `nodes` limits their total instead of the nodes of one thread.
+    pub node_counter: Option<Arc<AtomicU64>>,
+
+    /// Probability that pruned moves don't change the result, the bot chooses if unset
+    pub selectivity: Option<Selectivity>,
+}
+
+/// Confidence level of selective search, as in Edax. Lower levels prune more moves
+/// with ProbCut, so searches reach a greater depth in the same time.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub enum Selectivity {
+    Level73,
+    Level87,
+    Level95,
+    Level98,
+    Level99,
+    #[default]
+    Level100,
+}
+
+impl Selectivity {
+    /// All levels, from most to least selective
+    pub const ALL: [Selectivity; 6] = [
+        Selectivity::Level73,
+        Selectivity::Level87,
+        Selectivity::Level95,
+        Selectivity::Level98,
+        Selectivity::Level99,
+        Selectivity::Level100,
+    ];
+
+    /// Probability in percent that the result is the same as without pruning
+    pub fn percentage(&self) -> u32 {
+        match self {
+            Selectivity::Level73 => 73,
+            Selectivity::Level87 => 87,
+            Selectivity::Level95 => 95,
+            Selectivity::Level98 => 98,
+            Selectivity::Level99 => 99,
+            Selectivity::Level100 => 100,
+        }
+    }
+
+    /// Number of standard deviations a shallow search must exceed the window by to prune
+    pub fn probcut_t(&self) -> f64 {
+        match self {
+            Selectivity::Level73 => 1.1,
+            Selectivity::Level87 => 1.5,
+            Selectivity::Level95 => 2.0,
+            Selectivity::Level98 => 2.6,
+            Selectivity::Level99 => 3.3,
+            Selectivity::Level100 => 999.0,
+        }
+    }
+
+    pub fn from_percentage(percentage: u32) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|selectivity| selectivity.percentage() == percentage)
+    }
+}
+
+impl Display for Selectivity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}%", self.percentage())
+    }
+}
+
+/// Number of nodes between checks of the wall clock and stop flag, must be a power of two
+const NODES_PER_CHECK: u64 = 1024;
+
+/// Keeps track of a running search and tells when its limits are reached
+pub struct SearchBudget {
+    start: Instant,
+    limits: SearchLimits,
+
+    /// Nodes of this search added to the node counter of the limits so far
+    reported_nodes: Cell<u64>,
+
+    /// Nodes of all threads after the last addition to the node counter
+    shared_nodes: Cell<u64>,
+}
+
+impl SearchBudget {
+    pub fn new(limits: &SearchLimits) -> Self {
+        Self {
+            start: Instant::now(),
+            limits: limits.clone(),
+            reported_nodes: Cell::new(0),
+            shared_nodes: Cell::new(0),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn max_depth(&self, default: u32) -> u32 {
+        self.limits.depth.unwrap_or(default)
+    }
+
+    /// Returns true if the search should stop after `nodes` nodes.
+    /// The clock and stop flag are only checked every `NODES_PER_CHECK` nodes, and
+    /// nodes are added to the node counter of the limits in batches of that size, so
+    /// parallel searches may exceed the node limit by that many nodes per thread.
+    pub fn is_exhausted(&self, nodes: u64) -> bool {
+        let total = self.total_nodes(nodes);
+
+        if let Some(max_nodes) = self.limits.nodes {
+            if total >= max_nodes {
+                return true;
+            }
+        }
+
+        if nodes & (NODES_PER_CHECK - 1) != 0 {
+            return false;
+        }
+
+        if let Some(stop) = &self.limits.stop {
+            if stop.load(Ordering::Relaxed) {
+                return true;
+            }
+        }
+
+        if let Some(time) = self.limits.time {
+            if self.start.elapsed() >= time {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Returns the nodes of all threads sharing the node counter, or `nodes` without one
+    fn total_nodes(&self, nodes: u64) -> u64 {
+        let Some(counter) = &self.limits.node_counter else {
+            return nodes;
+        };
+
+        let unreported = nodes - self.reported_nodes.get();
+        if unreported >= NODES_PER_CHECK {
+            let shared = counter.fetch_add(unreported, Ordering::Relaxed) + unreported;
+            self.reported_nodes.set(nodes);
+            self.shared_nodes.set(shared);
+            return shared;
+        }
+
+        self.shared_nodes.get() + unreported
+    }
+}
+
+/// Names of all bots, some may be unavailable, see `available_bots`
+pub const BOT_NAMES: [&str; 3] = ["random", "squared", "edax"];
+
+/// Returns the bot named "random", "squared" or "edax", or None for other names and
+/// unavailable bots. The edax bot plays from the opening book if there is one.
 pub fn get_bot(name: &str) -> Option<Box<dyn Bot>> {
+    new_bot(name).ok()
+}
+
+/// Like `get_bot`, with an error telling why the bot is not available
+pub fn new_bot(name: &str) -> Result<Box<dyn Bot>, String> {
+    new_bot_with(name, &BotOptions::default())
+}
+
+/// Like `new_bot`, with the settings of `options` instead of the defaults
+pub fn new_bot_with(name: &str, options: &BotOptions) -> Result<Box<dyn Bot>, String> {
     match name {
-        "random" => Some(Box::new(RandomBot)),
-        "squared" => Some(Box::new(SquaredBot)),
-        "edax" => Some(Box::new(EdaxBot)),
-        _ => None,
+        "random" => Ok(Box::new(RandomBot)),
+        "squared" => Ok(Box::new(SquaredBot::new())),
+        "edax" => {
+            load_weights()?;
+            with_book(Box::new(EdaxBot::with_threads(options.threads)), options)
+        }
+        _ => Err(format!("Unknown bot {}", name)),
+    }
+}
+
+/// Returns the names of the bots `get_bot` can create. The edax bot is missing
+/// if its evaluation weights or the book can't be loaded.
+pub fn available_bots() -> Vec<&'static str> {
+    BOT_NAMES
+        .into_iter()
+        .filter(|&name| name != "edax" || is_edax_available())
+        .collect()
+}
+
+/// Returns true if the evaluation weights and the book of the edax bot load
+fn is_edax_available() -> bool {
+    load_weights().is_ok() && load_book().is_ok()
+}
+
+/// Settings of the bots created with `new_bot_with`
+#[derive(Clone, Debug)]
+pub struct BotOptions {
+    /// Threads searching in parallel for the edax bot
+    pub threads: usize,
+
+    /// Maximum number of discs a book move may score less than the best one, 0 always
+    /// plays a best book move
+    pub book_randomness: i32,
+}
+
+impl Default for BotOptions {
+    fn default() -> Self {
+        Self {
+            threads: 1,
+            book_randomness: 0,
+        }
     }
 }
 
-pub fn print_search_header(name: &'static str, is_endgame: bool, depth: u32) {
-    let search = if is_endgame { "endgame" } else { "midgame" };
-    println!("{} searching {} at depth {}", name, search, depth);
+/// Lets `bot` play from the book of `book::load_book`, if there is one
+fn with_book(bot: Box<dyn Bot>, options: &BotOptions) -> Result<Box<dyn Bot>, String> {
+    Ok(match load_book()? {
+        Some(book) => Box::new(BookBot::new(book, bot, options.book_randomness)),
+        None => bot,
+    })
+}
+
+/// Score of a move at the root of a search
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RootMoveScore {
+    pub move_: usize,
+    pub score: isize,
+
+    /// True if the move was refuted, so `score` is only an upper bound
+    pub is_upper_bound: bool,
+}
+
+/// Outcome of a search by a bot
+#[derive(Clone, PartialEq, Debug)]
+pub struct SearchResult {
+    pub best_move: usize,
+    pub score: isize,
+
+    /// Depth of the last completed iteration, 0 if the move was not searched
+    pub depth: u32,
+
+    /// True if the search reached the end of the game, the score is exact at 100% selectivity
+    pub is_endgame: bool,
+
+    /// True if the move was played from the opening book
+    pub is_book: bool,
+
+    /// Selectivity of the last completed iteration
+    pub selectivity: Selectivity,
+
+    /// Number of nodes searched in all iterations
+    pub nodes: u64,
+
+    /// Wall-clock time spent searching
+    pub duration: Duration,
+
+    /// Scores of all moves at the root in the last completed iteration
+    pub root_moves: Vec<RootMoveScore>,
+
+    /// Expected line of play starting with `best_move`, may contain `PASS`
+    pub principal_variation: Vec<usize>,
+}
+
+impl SearchResult {
+    /// Result for a move that was chosen without searching
+    pub fn unsearched(best_move: usize) -> Self {
+        Self {
+            best_move,
+            score: 0,
+            depth: 0,
+            is_endgame: false,
+            is_book: false,
+            selectivity: Selectivity::Level100,
+            nodes: 0,
+            duration: Duration::ZERO,
+            root_moves: vec![],
+            principal_variation: vec![best_move],
+        }
+    }
+
+    /// Nodes per second
+    pub fn speed(&self) -> u64 {
+        if self.duration.is_zero() {
+            return 0;
+        }
+
+        (self.nodes as f64 / self.duration.as_secs_f64()) as u64
+    }
+}
+
+impl Display for SearchResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_book {
+            writeln!(f, "Played from book")?;
+        } else {
+            let search = if self.is_endgame {
+                "endgame"
+            } else {
+                "midgame"
+            };
+            writeln!(
+                f,
+                "Searched {} at depth {} @ {}",
+                search, self.depth, self.selectivity
+            )?;
+        }
+
+        for (i, root_move) in self.root_moves.iter().enumerate() {
+            writeln!(
+                f,
+                "Move {:2}/{:2}: {} score {} {}",
+                i + 1,
+                self.root_moves.len(),
+                square_name(root_move.move_),
+                if root_move.is_upper_bound { "<=" } else { "==" },
+                format_score(root_move.score),
+            )?;
+        }
+
+        let pv: Vec<String> = self
+            .principal_variation
+            .iter()
+            .map(|&move_| square_name(move_))
+            .collect();
+
+        writeln!(
+            f,
+            "     Total: {} score == {} | {} / {:.3}s = {}/s",
+            square_name(self.best_move),
+            format_score(self.score),
+            format_nodes(self.nodes),
+            self.duration.as_secs_f64(),
+            format_nodes(self.speed()),
+        )?;
+        write!(f, "        PV: {}", pv.join(" "))
+    }
 }
 
 pub fn format_score(score: isize) -> String {
@@ -74,43 +461,132 @@ pub fn format_nodes(nodes: u64) -> String {
     }
 }
 
-pub fn print_move_stats(
-    nodes: u64,
-    current_move: usize,
-    total_moves: usize,
-    score: isize,
-    alpha: isize,
-    duration: Duration,
-) {
-    let speed = (nodes as f64 / duration.as_secs_f64()) as u64;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    println!(
-        "Move {:2}/{:2}: score {} {} | {} / {:.3}s = {}/s",
-        current_move + 1,
-        total_moves,
-        if score > alpha { "==" } else { "<=" },
-        format_score(score),
-        format_nodes(nodes),
-        duration.as_secs_f64(),
-        format_nodes(speed),
-    );
-}
+    #[test]
+    fn test_search_budget() {
+        let unlimited = SearchBudget::new(&SearchLimits::default());
+        assert!(!unlimited.is_exhausted(u64::MAX));
+        assert_eq!(unlimited.max_depth(10), 10);
 
-pub fn print_total_stats(total_nodes: u64, total_duration: Duration) {
-    let speed = (total_nodes as f64 / total_duration.as_secs_f64()) as u64;
+        let limits = SearchLimits {
+            nodes: Some(1000),
+            depth: Some(4),
+            ..Default::default()
+        };
+        let budget = SearchBudget::new(&limits);
+        assert!(!budget.is_exhausted(999));
+        assert!(budget.is_exhausted(1000));
+        assert_eq!(budget.max_depth(10), 4);
 
-    println!(
-        "     Total:               | {} / {:.3}s = {}/s",
-        format_nodes(total_nodes),
-        total_duration.as_secs_f64(),
-        format_nodes(speed),
-    );
-    println!();
-}
+        let limits = SearchLimits {
+            time: Some(Duration::ZERO),
+            ..Default::default()
+        };
+        assert!(SearchBudget::new(&limits).is_exhausted(0));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let stop = Arc::new(AtomicBool::new(false));
+        let limits = SearchLimits {
+            stop: Some(stop.clone()),
+            ..Default::default()
+        };
+        let budget = SearchBudget::new(&limits);
+        assert!(!budget.is_exhausted(0));
+
+        stop.store(true, Ordering::Relaxed);
+        assert!(budget.is_exhausted(0));
+    }
+
+    #[test]
+    fn test_search_budget_node_counter() {
+        let counter = Arc::new(AtomicU64::new(0));
+        let limits = SearchLimits {
+            nodes: Some(3 * NODES_PER_CHECK),
+            node_counter: Some(counter.clone()),
+            ..Default::default()
+        };
+        let first = SearchBudget::new(&limits);
+        let second = SearchBudget::new(&limits);
+
+        // Nodes are added to the counter in batches, the limit applies to the total
+        assert!(!first.is_exhausted(NODES_PER_CHECK - 1));
+        assert_eq!(counter.load(Ordering::Relaxed), 0);
+        assert!(!first.is_exhausted(NODES_PER_CHECK));
+        assert_eq!(counter.load(Ordering::Relaxed), NODES_PER_CHECK);
+
+        assert!(!second.is_exhausted(NODES_PER_CHECK));
+        assert!(!first.is_exhausted(NODES_PER_CHECK + 1));
+        assert!(first.is_exhausted(2 * NODES_PER_CHECK));
+        assert_eq!(counter.load(Ordering::Relaxed), 3 * NODES_PER_CHECK);
+    }
+
+    #[test]
+    fn test_get_bot() {
+        for name in available_bots() {
+            assert!(get_bot(name).is_some());
+        }
+        assert!(available_bots().contains(&"random"));
+        assert_eq!(available_bots().contains(&"edax"), is_edax_available());
+
+        assert!(get_bot("human").is_none());
+        assert_eq!(
+            new_bot("human").err(),
+            Some("Unknown bot human".to_string())
+        );
+    }
+
+    #[test]
+    fn test_selectivity() {
+        assert_eq!(Selectivity::default(), Selectivity::Level100);
+        assert_eq!(Selectivity::from_percentage(95), Some(Selectivity::Level95));
+        assert_eq!(Selectivity::from_percentage(50), None);
+        assert_eq!(Selectivity::Level73.to_string(), "73%");
+
+        // Higher levels prune less
+        for pair in Selectivity::ALL.windows(2) {
+            assert!(pair[0] < pair[1]);
+            assert!(pair[0].probcut_t() < pair[1].probcut_t());
+        }
+    }
+
+    #[test]
+    fn test_search_result_display() {
+        let result = SearchResult {
+            best_move: 19,
+            score: 2,
+            depth: 3,
+            is_endgame: false,
+            is_book: false,
+            selectivity: Selectivity::Level87,
+            nodes: 1234,
+            duration: Duration::from_millis(500),
+            root_moves: vec![
+                RootMoveScore {
+                    move_: 19,
+                    score: 2,
+                    is_upper_bound: false,
+                },
+                RootMoveScore {
+                    move_: 26,
+                    score: 2,
+                    is_upper_bound: true,
+                },
+            ],
+            principal_variation: vec![19, 18, 64],
+        };
+
+        assert_eq!(result.speed(), 2468);
+
+        let expected = "\
+Searched midgame at depth 3 @ 87%
+Move  1/ 2: d3 score ==    2
+Move  2/ 2: c4 score <=    2
+     Total: d3 score ==    2 | 1.23kn / 0.500s = 2.47kn/s
+        PV: d3 c3 pass";
+        assert_eq!(result.to_string(), expected);
+    }
 
     #[test]
     fn test_format_score() {
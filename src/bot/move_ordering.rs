@@ -0,0 +1,272 @@
+// This is adapted from https://github.com/abulmo/edax-reversi/blob/master/src/move.c
+
+use crate::othello::do_move::get_flipped;
+use crate::othello::get_moves::get_moves;
+use crate::othello::position::Position;
+use crate::othello::stability::get_stable_edges;
+
+/// Maximum number of valid moves in any position
+pub const MAX_MOVES: usize = 33;
+
+const CORNERS: u64 = 0x8100000000000081;
+
+/// Weights of the terms of a move score
+const W_WIPEOUT: i32 = 1 << 30;
+const W_HASH: i32 = 1 << 29;
+const W_SEARCH: i32 = 1 << 15;
+const W_MOBILITY: i32 = 1 << 15;
+const W_CORNER_STABILITY: i32 = 1 << 11;
+const W_EDGE_STABILITY: i32 = 1 << 11;
+const W_POTENTIAL_MOBILITY: i32 = 1 << 5;
+
+/// Bonus for moves into a region with an odd number of empties
+pub const W_PARITY: i32 = 1 << 3;
+
+/// Move ordering bonus per square type
+#[rustfmt::skip]
+const SQUARE_VALUE: [i32; 64] = [
+    18,  4, 16, 12, 12, 16,  4, 18,
+     4,  2,  6,  8,  8,  6,  2,  4,
+    16,  6, 14, 10, 10, 14,  6, 16,
+    12,  8, 10,  0,  0, 10,  8, 12,
+    12,  8, 10,  0,  0, 10,  8, 12,
+    16,  6, 14, 10, 10, 14,  6, 16,
+     4,  2,  6,  8,  8,  6,  2,  4,
+    18,  4, 16, 12, 12, 16,  4, 18,
+];
+
+/// Counts the squares of a bitboard, corners count double
+fn weighted_count(bitboard: u64) -> i32 {
+    (bitboard.count_ones() + (bitboard & CORNERS).count_ones()) as i32
+}
+
+/// Returns empty squares next to an opponent disc that is not on an edge,
+/// these may become moves later
+fn get_potential_moves(opponent: u64, empties: u64) -> u64 {
+    (((opponent & 0x7e7e7e7e7e7e7e7e) << 1)
+        | ((opponent & 0x7e7e7e7e7e7e7e7e) >> 1)
+        | ((opponent & 0x00ffffffffffff00) << 8)
+        | ((opponent & 0x00ffffffffffff00) >> 8)
+        | ((opponent & 0x007e7e7e7e7e7e00) << 7)
+        | ((opponent & 0x007e7e7e7e7e7e00) >> 7)
+        | ((opponent & 0x007e7e7e7e7e7e00) << 9)
+        | ((opponent & 0x007e7e7e7e7e7e00) >> 9))
+        & empties
+}
+
+/// Counts the discs of `player` that are corners or edge squares next to an own corner
+fn get_corner_stability(player: u64) -> i32 {
+    let stable = (((0x0100000000000001 & player) << 1)
+        | ((0x8000000000000080 & player) >> 1)
+        | ((0x0000000000000081 & player) << 8)
+        | ((0x8100000000000000 & player) >> 8)
+        | CORNERS)
+        & player;
+    stable.count_ones() as i32
+}
+
+/// Returns the depth of the shallow search that orders moves of a search at `depth`.
+/// Depth 0 means the evaluation of the child is used, `None` means only heuristics.
+pub fn sort_depth(depth: u32) -> Option<u32> {
+    match depth {
+        0..=2 => None,
+        3..=5 => Some(0),
+        _ => Some((depth - 4) / 2),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct ScoredMove {
+    pub move_: usize,
+
+    /// Discs flipped by the move
+    pub flipped: u64,
+
+    /// Higher scores are searched first
+    pub score: i32,
+}
+
+/// Valid moves of a position, which can be scored and searched best-first.
+pub struct MoveList {
+    moves: [ScoredMove; MAX_MOVES],
+    len: usize,
+}
+
+impl MoveList {
+    /// Returns all valid moves in bit order, without scores
+    pub fn new(position: &Position) -> Self {
+        let mut list = Self {
+            moves: [ScoredMove::default(); MAX_MOVES],
+            len: 0,
+        };
+
+        let mut remaining_moves = position.get_moves();
+        while remaining_moves != 0 {
+            let move_ = remaining_moves.trailing_zeros() as usize;
+            remaining_moves &= remaining_moves - 1;
+
+            list.moves[list.len] = ScoredMove {
+                move_,
+                flipped: get_flipped(position.player, position.opponent, move_),
+                score: 0,
+            };
+            list.len += 1;
+        }
+
+        list
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ScoredMove> {
+        self.moves[..self.len].iter()
+    }
+
+    /// Scores moves with heuristics. Moves taking all opponent discs come first,
+    /// then `hash_move`, then moves that leave the opponent with few moves.
+    pub fn evaluate(&mut self, position: &Position, hash_move: usize) {
+        for scored in &mut self.moves[..self.len] {
+            scored.score = if scored.flipped == position.opponent {
+                W_WIPEOUT
+            } else if scored.move_ == hash_move {
+                W_HASH
+            } else {
+                Self::evaluate_move(position, scored.move_, scored.flipped)
+            };
+        }
+    }
+
+    fn evaluate_move(position: &Position, move_: usize, flipped: u64) -> i32 {
+        let next_player = position.opponent ^ flipped;
+        let next_opponent = position.player | flipped | (1u64 << move_);
+        let empties = !(next_player | next_opponent);
+
+        let mut score = SQUARE_VALUE[move_];
+
+        let potential_mobility = weighted_count(get_potential_moves(next_opponent, empties));
+        score += (36 - potential_mobility) * W_POTENTIAL_MOBILITY;
+
+        score += get_corner_stability(next_opponent) * W_CORNER_STABILITY;
+
+        let edge_stability = get_stable_edges(next_opponent, next_player).count_ones() as i32;
+        score += edge_stability * W_EDGE_STABILITY;
+
+        let mobility = weighted_count(get_moves(next_player, next_opponent));
+        score += (36 - mobility) * W_MOBILITY;
+
+        score
+    }
+
+    /// Adds `bonus` to the score of moves on `squares`
+    pub fn add_bonus(&mut self, squares: u64, bonus: i32) {
+        for scored in &mut self.moves[..self.len] {
+            if squares & (1u64 << scored.move_) != 0 {
+                scored.score += bonus;
+            }
+        }
+    }
+
+    /// Adds shallow search results to the score of all moves except the wipeouts and
+    /// hash move. `search` returns the score of a move for the player making it, in discs.
+    pub fn add_search_scores(&mut self, mut search: impl FnMut(usize, u64) -> i32) {
+        for scored in &mut self.moves[..self.len] {
+            if scored.score >= W_HASH {
+                continue;
+            }
+
+            let score = search(scored.move_, scored.flipped).clamp(-64, 64);
+            scored.score += ((score + 64) >> 2) * W_SEARCH;
+        }
+    }
+
+    /// Sorts the moves by descending score, keeping the order of equal scores
+    pub fn sort(&mut self) {
+        self.moves[..self.len].sort_by_key(|scored| -scored.score);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::hash_table::NO_MOVE;
+
+    #[test]
+    fn test_move_list() {
+        let position = Position::new();
+        let list = MoveList::new(&position);
+
+        assert_eq!(list.len(), 4);
+        assert_eq!(
+            list.iter().map(|m| m.move_).collect::<Vec<_>>(),
+            vec![19, 26, 37, 44]
+        );
+
+        for scored in list.iter() {
+            let mut child = position;
+            assert_eq!(child.do_move(scored.move_), scored.flipped);
+        }
+
+        assert!(MoveList::new(&Position::new_from_bitboards(0, 0)).is_empty());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let position = Position::new_xot();
+        let mut list = MoveList::new(&position);
+        let hash_move = list.iter().last().unwrap().move_;
+
+        list.evaluate(&position, hash_move);
+        list.sort();
+        assert_eq!(list.iter().next().unwrap().move_, hash_move);
+
+        // Sorted by descending score
+        let scores: Vec<_> = list.iter().map(|m| m.score).collect();
+        assert!(scores.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+
+    #[test]
+    fn test_wipeout_first() {
+        // Playing a1 flips the only opponent disc
+        let position = Position::new_from_bitboards(0x4, 0x2);
+        let mut list = MoveList::new(&position);
+
+        list.evaluate(&position, NO_MOVE as usize);
+        list.sort();
+        assert_eq!(list.iter().next().unwrap().move_, 0);
+        assert_eq!(list.iter().next().unwrap().score, W_WIPEOUT);
+    }
+
+    #[test]
+    fn test_add_search_scores() {
+        let position = Position::new_xot();
+        let mut list = MoveList::new(&position);
+        list.evaluate(&position, NO_MOVE as usize);
+        let before: Vec<_> = list.iter().map(|m| m.score).collect();
+
+        let best = list.iter().last().unwrap().move_;
+        list.add_search_scores(|move_, _| if move_ == best { 64 } else { -64 });
+
+        for (scored, before) in list.iter().zip(before) {
+            let bonus = if scored.move_ == best {
+                32 * W_SEARCH
+            } else {
+                0
+            };
+            assert_eq!(scored.score, before + bonus);
+        }
+    }
+
+    #[test]
+    fn test_sort_depth() {
+        assert_eq!(sort_depth(1), None);
+        assert_eq!(sort_depth(3), Some(0));
+        assert_eq!(sort_depth(6), Some(1));
+        assert_eq!(sort_depth(10), Some(3));
+    }
+}
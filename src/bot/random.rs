@@ -3,13 +3,18 @@ use rand::RngCore;
 
 use crate::othello::position::Position;
 
-use super::Bot;
+use super::{Bot, SearchLimits, SearchObserver, SearchResult};
 
 pub struct RandomBot;
 
 impl Bot for RandomBot {
-    // Returns the index of a random valid move
-    fn get_move(&mut self, position: &Position) -> usize {
+    // Returns a random valid move
+    fn search(
+        &mut self,
+        position: &Position,
+        _limits: &SearchLimits,
+        _observer: &mut dyn SearchObserver,
+    ) -> SearchResult {
         let moves = position.get_moves();
 
         if moves == 0 {
@@ -28,7 +33,7 @@ impl Bot for RandomBot {
             remaining -= 1;
         }
 
-        current_moves.trailing_zeros() as usize
+        SearchResult::unsearched(current_moves.trailing_zeros() as usize)
     }
 }
 
@@ -44,7 +49,7 @@ mod tests {
 
         // Call get_move 10 times and verify each move is valid
         for _ in 0..10 {
-            let selected_move = bot.get_move(&position);
+            let selected_move = bot.get_move(&position, &SearchLimits::default());
             assert!(
                 position.is_valid_move(selected_move),
                 "Move {} was invalid! Valid moves: {:b}",
@@ -60,6 +65,6 @@ mod tests {
         let position = Position::new_from_bitboards(0, 0); // Empty position has no moves
         let mut bot = RandomBot;
 
-        bot.get_move(&position); // Should panic when there are no valid moves
+        bot.get_move(&position, &SearchLimits::default()); // Should panic when there are no valid moves
     }
 }
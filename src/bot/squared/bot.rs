@@ -2,17 +2,42 @@
 
 use super::endgame::EndgameSearch;
 use super::midgame::MidgameSearch;
+use crate::bot::hash_table::{HashTable, DEFAULT_HASH_TABLE_BITS};
 use crate::othello::position::Position;
 
-use crate::bot::Bot;
+use crate::bot::{Bot, SearchLimits, SearchObserver, SearchResult};
 
-pub struct SquaredBot;
+pub struct SquaredBot {
+    // Midgame scores are scaled by 1000, so endgame results get their own table
+    midgame_table: HashTable,
+    endgame_table: HashTable,
+}
 
 pub static MIDGAME_DEPTH: u32 = 8;
 pub static ENDGAME_DEPTH: u32 = 18;
 
+impl Default for SquaredBot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SquaredBot {
+    pub fn new() -> Self {
+        Self {
+            midgame_table: HashTable::new(DEFAULT_HASH_TABLE_BITS),
+            endgame_table: HashTable::new(DEFAULT_HASH_TABLE_BITS),
+        }
+    }
+}
+
 impl Bot for SquaredBot {
-    fn get_move(&mut self, position: &Position) -> usize {
+    fn search(
+        &mut self,
+        position: &Position,
+        limits: &SearchLimits,
+        observer: &mut dyn SearchObserver,
+    ) -> SearchResult {
         let moves = position.get_moves();
 
         if moves == 0 {
@@ -20,14 +45,16 @@ impl Bot for SquaredBot {
         }
 
         if moves.count_ones() == 1 {
-            return moves.trailing_zeros() as usize;
+            return SearchResult::unsearched(moves.trailing_zeros() as usize);
         }
 
         if position.count_empty() > ENDGAME_DEPTH {
-            let mut search = MidgameSearch::new(*position);
-            return search.get_move();
+            self.midgame_table.new_search();
+            let mut search = MidgameSearch::new(*position, &self.midgame_table);
+            return search.search(limits, observer);
         }
 
-        EndgameSearch::new().get_move(position)
+        self.endgame_table.new_search();
+        EndgameSearch::new(&self.endgame_table).search(position, limits, observer)
     }
 }
@@ -1,67 +1,112 @@
-use std::time::{Duration, Instant};
-
 use crate::{
-    bot::{print_move_stats, print_search_header, print_total_stats},
+    bot::{
+        hash_table::{HashTable, NO_MOVE},
+        RootMoveScore, SearchBudget, SearchEvent, SearchLimits, SearchObserver, SearchResult,
+        Selectivity,
+    },
     othello::position::Position,
 };
 
 pub static MIN_ENDGAME_SCORE: isize = -64;
 pub static MAX_ENDGAME_SCORE: isize = 64;
 
-pub struct EndgameSearch {
+/// Positions with fewer empty squares are not looked up in the hash table
+const MIN_HASH_EMPTIES: u32 = 6;
+
+pub struct EndgameSearch<'a> {
     nodes: u64,
     position: Position,
+    hash_table: &'a HashTable,
+    budget: SearchBudget,
+    aborted: bool,
 }
 
-impl Default for EndgameSearch {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-impl EndgameSearch {
-    pub fn new() -> Self {
+impl<'a> EndgameSearch<'a> {
+    pub fn new(hash_table: &'a HashTable) -> Self {
         Self {
             nodes: 0,
             position: Position::new(),
+            hash_table,
+            budget: SearchBudget::new(&SearchLimits::default()),
+            aborted: false,
         }
     }
 
-    // TODO #5 bring from Edax, make this private again
-    pub fn get_move(&mut self, position: &Position) -> usize {
+    /// Solves the position exactly. If the budget runs out, the result only contains
+    /// the completely searched children and has depth 0.
+    pub(super) fn search(
+        &mut self,
+        position: &Position,
+        limits: &SearchLimits,
+        observer: &mut dyn SearchObserver,
+    ) -> SearchResult {
         let children = position.children_with_index();
 
-        let mut best_move = children.first().unwrap().0;
-        let mut alpha = MIN_ENDGAME_SCORE;
+        let mut result = SearchResult::unsearched(children.first().unwrap().0);
+        result.is_endgame = true;
 
-        let mut total_nodes = 0;
-        let mut total_duration = Duration::ZERO;
+        // Below any possible score, so the first move always gets an exact score
+        let mut alpha = MIN_ENDGAME_SCORE - 1;
 
-        print_search_header("SquaredBot", true, position.count_empty());
-        for (i, (move_, child)) in children.iter().enumerate() {
-            let start = Instant::now();
+        self.budget = SearchBudget::new(limits);
+        self.aborted = false;
+        self.nodes = 0;
+
+        for (move_, child) in &children {
             self.position = *child;
             let score = -self.negamax(-MAX_ENDGAME_SCORE, -alpha);
-            let duration = start.elapsed();
 
-            print_move_stats(self.nodes, i, children.len(), score, alpha, duration);
-            total_nodes += self.nodes;
-            total_duration += duration;
-            self.nodes = 0;
+            if self.aborted {
+                break;
+            }
+
+            let root_move = RootMoveScore {
+                move_: *move_,
+                score,
+                is_upper_bound: score <= alpha,
+            };
+            result.root_moves.push(root_move);
+            observer.notify(SearchEvent::RootMove {
+                depth: position.count_empty(),
+                root_move,
+            });
 
             if score > alpha {
                 alpha = score;
-                best_move = *move_;
+                result.best_move = *move_;
+                result.score = score;
             }
         }
 
-        print_total_stats(total_nodes, total_duration);
-        best_move
+        if !self.aborted {
+            result.depth = position.count_empty();
+        }
+
+        result.principal_variation = self.hash_table.principal_variation(
+            position,
+            result.best_move,
+            position.count_empty() as usize,
+        );
+        result.nodes = self.nodes;
+        result.duration = self.budget.elapsed();
+
+        if !self.aborted {
+            observer.notify(SearchEvent::DepthCompleted(&result));
+        }
+
+        result
     }
 
     fn negamax(&mut self, mut alpha: isize, beta: isize) -> isize {
         self.nodes += 1;
 
+        if self.budget.is_exhausted(self.nodes) {
+            self.aborted = true;
+        }
+        if self.aborted {
+            return 0;
+        }
+
         let mut remaining_moves = self.position.get_moves();
 
         // If no moves available
@@ -79,30 +124,130 @@ impl EndgameSearch {
             return score;
         }
 
-        while remaining_moves != 0 {
-            let move_ = remaining_moves.trailing_zeros() as usize;
+        let n_empties = self.position.count_empty();
+        if n_empties < MIN_HASH_EMPTIES {
+            while remaining_moves != 0 {
+                let move_ = remaining_moves.trailing_zeros() as usize;
+
+                let flipped = self.position.do_move(move_);
+                let score = -self.negamax(-beta, -alpha);
+                self.position.undo_move(move_, flipped);
+
+                if self.aborted {
+                    return 0;
+                }
+
+                alpha = alpha.max(score);
+
+                if alpha >= beta {
+                    break; // Beta cutoff
+                }
+
+                remaining_moves &= remaining_moves - 1;
+            }
+
+            return alpha;
+        }
+
+        // Endgame entries are exact solves, so their depth is the number of empties
+        let mut hash_move = NO_MOVE as usize;
+        if let Some(entry) = self.hash_table.get(&self.position) {
+            if entry.depth >= n_empties {
+                if entry.lower as isize >= beta {
+                    return entry.lower as isize;
+                }
+                if entry.upper as isize <= alpha {
+                    return entry.upper as isize;
+                }
+                if entry.lower == entry.upper {
+                    return entry.lower as isize;
+                }
+            }
+            hash_move = entry.best_move as usize;
+        }
+
+        let original_alpha = alpha;
+        let mut best_score = MIN_ENDGAME_SCORE - 1;
+        let mut best_move = NO_MOVE as usize;
+
+        // Search the hash move first, then the remaining moves in bit order
+        let mut move_ = if hash_move < 64 && remaining_moves & (1u64 << hash_move) != 0 {
+            hash_move
+        } else {
+            remaining_moves.trailing_zeros() as usize
+        };
+
+        loop {
+            remaining_moves &= !(1u64 << move_);
 
             let flipped = self.position.do_move(move_);
             let score = -self.negamax(-beta, -alpha);
             self.position.undo_move(move_, flipped);
 
-            alpha = alpha.max(score);
+            if self.aborted {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = move_;
+                alpha = alpha.max(score);
 
-            if alpha >= beta {
-                break; // Beta cutoff
+                if alpha >= beta {
+                    break; // Beta cutoff
+                }
             }
 
-            remaining_moves &= remaining_moves - 1;
+            if remaining_moves == 0 {
+                break;
+            }
+            move_ = remaining_moves.trailing_zeros() as usize;
         }
 
-        alpha
+        self.hash_table.store(
+            &self.position,
+            n_empties,
+            Selectivity::Level100,
+            original_alpha as i32,
+            beta as i32,
+            best_score as i32,
+            best_move,
+        );
+
+        best_score
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bot::format_nodes;
     use crate::othello::ffo_problems::parse_ffo_problems;
+    use std::time::Instant;
+
+    #[test]
+    fn test_search_result() {
+        let problem = parse_ffo_problems()
+            .into_iter()
+            .find(|p| p.depth <= 8)
+            .unwrap();
+
+        let hash_table = HashTable::new(10);
+        let mut search = EndgameSearch::new(&hash_table);
+        let result = search.search(
+            &problem.position,
+            &SearchLimits::default(),
+            &mut |_: SearchEvent| {},
+        );
+
+        let best_score = *problem.solutions.values().max().unwrap();
+        assert_eq!(result.score, best_score);
+        assert_eq!(problem.solutions[&result.best_move], best_score);
+        assert_eq!(result.depth, problem.depth);
+        assert!(result.is_endgame);
+        assert_eq!(result.root_moves.len(), problem.solutions.len());
+        assert_eq!(result.principal_variation[0], result.best_move);
+    }
 
     #[test]
     fn test_ffo_problems() {
@@ -134,14 +279,21 @@ mod tests {
                 .for_each(|(&move_, &expected_score)| {
                     let child = problem.position.do_move_cloned(move_);
 
-                    let mut search = EndgameSearch::new();
+                    let hash_table = HashTable::new(16);
+                    let mut search = EndgameSearch::new(&hash_table);
                     search.position = child;
 
                     let start = Instant::now();
                     let score = -search.negamax(MIN_ENDGAME_SCORE, MAX_ENDGAME_SCORE);
                     let duration = start.elapsed();
 
-                    print_move_stats(search.nodes, 0, 1, score, MIN_ENDGAME_SCORE, duration);
+                    println!(
+                        "Move {:2}: score {:3} | {} / {:.3}s",
+                        move_,
+                        score,
+                        format_nodes(search.nodes),
+                        duration.as_secs_f64(),
+                    );
 
                     assert_eq!(
                         score,
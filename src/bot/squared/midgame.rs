@@ -1,64 +1,139 @@
-use std::time::{Duration, Instant};
-
 use crate::{
-    bot::{print_move_stats, print_search_header, print_total_stats, squared::bot::MIDGAME_DEPTH},
+    bot::{
+        hash_table::{HashTable, NO_MOVE},
+        move_ordering::{sort_depth, MoveList},
+        squared::bot::MIDGAME_DEPTH,
+        RootMoveScore, SearchBudget, SearchEvent, SearchLimits, SearchObserver, SearchResult,
+        Selectivity,
+    },
     othello::position::Position,
 };
 
 static MIN_MIDGAME_SCORE: isize = -64000;
 static MAX_MIDGAME_SCORE: isize = 64000;
 
-pub struct MidgameSearch {
+pub struct MidgameSearch<'a> {
     nodes: u64,
     position: Position,
+    hash_table: &'a HashTable,
+    budget: SearchBudget,
+    aborted: bool,
 }
 
-impl MidgameSearch {
-    pub fn new(position: Position) -> Self {
-        Self { nodes: 0, position }
+impl<'a> MidgameSearch<'a> {
+    pub fn new(position: Position, hash_table: &'a HashTable) -> Self {
+        Self {
+            nodes: 0,
+            position,
+            hash_table,
+            budget: SearchBudget::new(&SearchLimits::default()),
+            aborted: false,
+        }
     }
 
-    pub fn get_move(&mut self) -> usize {
-        let children = self.position.children_with_index();
-        let mut best_move = children.first().unwrap().0;
-        let mut alpha = MIN_MIDGAME_SCORE;
+    /// Searches with iterative deepening until the maximum depth or until the
+    /// budget runs out, returning the result of the last completed iteration.
+    pub fn search(
+        &mut self,
+        limits: &SearchLimits,
+        observer: &mut dyn SearchObserver,
+    ) -> SearchResult {
+        let mut children = self.position.children_with_index();
+
+        let mut result = SearchResult::unsearched(children.first().unwrap().0);
+
+        self.budget = SearchBudget::new(limits);
+        self.aborted = false;
+        self.nodes = 0;
+
+        let max_depth = self
+            .budget
+            .max_depth(MIDGAME_DEPTH)
+            .min(self.position.count_empty());
 
-        let mut total_nodes = 0;
-        let mut total_duration = Duration::ZERO;
+        for depth in 1..=max_depth.max(1) {
+            // Search the best move of the previous iteration first
+            children.sort_by_key(|(move_, _)| *move_ != result.best_move);
 
-        print_search_header("SquaredBot", false, MIDGAME_DEPTH);
-        for (i, (move_, child)) in children.iter().enumerate() {
-            let start = Instant::now();
-            let score = -self.negamax(child, MIDGAME_DEPTH - 1, -MAX_MIDGAME_SCORE, -alpha);
-            let duration = start.elapsed();
+            let Some(root_moves) = self.search_root(&children, depth, observer) else {
+                break;
+            };
 
-            print_move_stats(self.nodes, i, children.len(), score, alpha, duration);
-            total_nodes += self.nodes;
-            total_duration += duration;
-            self.nodes = 0;
+            // Each move that raised alpha has an exact score, the last one is the best
+            let best = *root_moves.iter().rev().find(|m| !m.is_upper_bound).unwrap();
+
+            result.best_move = best.move_;
+            result.score = best.score;
+            result.depth = depth;
+            result.principal_variation =
+                self.hash_table
+                    .principal_variation(&self.position, best.move_, depth as usize);
+            result.root_moves = root_moves;
+            result.nodes = self.nodes;
+            result.duration = self.budget.elapsed();
+
+            observer.notify(SearchEvent::DepthCompleted(&result));
+        }
+
+        result.nodes = self.nodes;
+        result.duration = self.budget.elapsed();
+        result
+    }
+
+    /// Returns the scores of all moves at `depth`, or `None` if the budget ran out.
+    fn search_root(
+        &mut self,
+        children: &[(usize, Position)],
+        depth: u32,
+        observer: &mut dyn SearchObserver,
+    ) -> Option<Vec<RootMoveScore>> {
+        let mut root_moves = Vec::with_capacity(children.len());
+
+        // Below any possible score, so the first move always gets an exact score
+        let mut alpha = MIN_MIDGAME_SCORE - 1;
+
+        for (move_, child) in children {
+            let score = -self.negamax(child, depth - 1, -MAX_MIDGAME_SCORE, -alpha);
+
+            if self.aborted {
+                return None;
+            }
+
+            let root_move = RootMoveScore {
+                move_: *move_,
+                score,
+                is_upper_bound: score <= alpha,
+            };
+            root_moves.push(root_move);
+            observer.notify(SearchEvent::RootMove { depth, root_move });
 
             if score > alpha {
                 alpha = score;
-                best_move = *move_;
             }
         }
 
-        print_total_stats(total_nodes, total_duration);
-
-        best_move
+        Some(root_moves)
     }
 
     fn negamax(&mut self, position: &Position, depth: u32, mut alpha: isize, beta: isize) -> isize {
+        // Move ordering may start more searches after the budget ran out
+        if self.aborted {
+            return 0;
+        }
+
         self.nodes += 1;
 
+        if self.budget.is_exhausted(self.nodes) {
+            self.aborted = true;
+            return 0;
+        }
+
         if depth == 0 {
             return Self::heuristic(position);
         }
 
-        let children = position.children();
-
         // If no moves available
-        if children.is_empty() {
+        if !position.has_moves() {
             // Check if the game is finished
             let mut passed_position = *position;
             passed_position.pass();
@@ -72,16 +147,78 @@ impl MidgameSearch {
             return -self.negamax(&passed_position, depth - 1, -beta, -alpha);
         }
 
-        for child in &children {
-            let score = -self.negamax(child, depth - 1, -beta, -alpha);
-            alpha = alpha.max(score);
+        let mut hash_move = NO_MOVE as usize;
+        if let Some(entry) = self.hash_table.get(position) {
+            if entry.depth >= depth {
+                if entry.lower as isize >= beta {
+                    return entry.lower as isize;
+                }
+                if entry.upper as isize <= alpha {
+                    return entry.upper as isize;
+                }
+                if entry.lower == entry.upper {
+                    return entry.lower as isize;
+                }
+            }
+            hash_move = entry.best_move as usize;
+        }
+
+        let moves = self.sorted_moves(position, depth, hash_move);
+        if self.aborted {
+            return 0;
+        }
+
+        let original_alpha = alpha;
+        let mut best_score = MIN_MIDGAME_SCORE - 1;
+        let mut best_move = NO_MOVE as usize;
 
-            if alpha >= beta {
-                break; // Beta cutoff
+        for scored in moves.iter() {
+            let child = position.do_move_cloned(scored.move_);
+            let score = -self.negamax(&child, depth - 1, -beta, -alpha);
+
+            if self.aborted {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = scored.move_;
+                alpha = alpha.max(score);
+
+                if alpha >= beta {
+                    break; // Beta cutoff
+                }
             }
         }
 
-        alpha
+        self.hash_table.store(
+            position,
+            depth,
+            Selectivity::Level100,
+            original_alpha as i32,
+            beta as i32,
+            best_score as i32,
+            best_move,
+        );
+
+        best_score
+    }
+
+    /// Returns the valid moves, best first according to the hash move, heuristics
+    /// and a shallow search if `depth` is high enough.
+    fn sorted_moves(&mut self, position: &Position, depth: u32, hash_move: usize) -> MoveList {
+        let mut moves = MoveList::new(position);
+        moves.evaluate(position, hash_move);
+
+        if let Some(sort_depth) = sort_depth(depth) {
+            moves.add_search_scores(|move_, _| {
+                let child = position.do_move_cloned(move_);
+                -self.negamax(&child, sort_depth, MIN_MIDGAME_SCORE, MAX_MIDGAME_SCORE) as i32
+            });
+        }
+
+        moves.sort();
+        moves
     }
 
     fn heuristic(position: &Position) -> isize {
@@ -101,3 +238,168 @@ impl MidgameSearch {
         (3 * corner_diff) + move_diff
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::othello::position::XOT_POSITIONS;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_get_move_with_depth_limit() {
+        let position = Position::new();
+        let hash_table = HashTable::new(10);
+        let mut search = MidgameSearch::new(position, &hash_table);
+
+        let limits = SearchLimits {
+            depth: Some(2),
+            ..Default::default()
+        };
+        let best_move = search.search(&limits, &mut |_: SearchEvent| {}).best_move;
+
+        assert!(position.is_valid_move(best_move));
+        assert!(!search.aborted);
+    }
+
+    #[test]
+    fn test_search_result() {
+        let position = Position::new_xot();
+        let hash_table = HashTable::new(16);
+        let mut search = MidgameSearch::new(position, &hash_table);
+
+        let limits = SearchLimits {
+            depth: Some(4),
+            ..Default::default()
+        };
+        let result = search.search(&limits, &mut |_: SearchEvent| {});
+
+        assert_eq!(result.depth, 4);
+        assert!(!result.is_endgame);
+        assert_eq!(result.nodes, search.nodes);
+        assert_eq!(result.root_moves.len(), position.children().len());
+        assert_eq!(result.principal_variation[0], result.best_move);
+        assert!(result.principal_variation.len() <= 4);
+
+        // The principal variation is a sequence of valid moves
+        let mut pv_position = position;
+        for &move_ in &result.principal_variation {
+            assert!(pv_position.is_valid_move(move_));
+            pv_position.do_move(move_);
+        }
+    }
+
+    #[test]
+    fn test_node_count() {
+        // Guards move ordering against regressions
+        let positions = [Position::new(), XOT_POSITIONS[0], XOT_POSITIONS[1]];
+        let expected_nodes = [2159, 9568, 14244];
+
+        for (position, expected_nodes) in positions.into_iter().zip(expected_nodes) {
+            let hash_table = HashTable::new(16);
+            let mut search = MidgameSearch::new(position, &hash_table);
+
+            let limits = SearchLimits {
+                depth: Some(7),
+                ..Default::default()
+            };
+            let result = search.search(&limits, &mut |_: SearchEvent| {});
+
+            assert_eq!(result.nodes, expected_nodes);
+        }
+    }
+
+    #[test]
+    fn test_get_move_with_node_limit() {
+        let position = XOT_POSITIONS[0];
+        let hash_table = HashTable::new(10);
+        let mut search = MidgameSearch::new(position, &hash_table);
+
+        let limits = SearchLimits {
+            depth: Some(30),
+            nodes: Some(5000),
+            ..Default::default()
+        };
+        let best_move = search.search(&limits, &mut |_: SearchEvent| {}).best_move;
+
+        assert!(position.is_valid_move(best_move));
+        assert!(search.aborted);
+        assert_eq!(search.nodes, 5000);
+    }
+
+    #[test]
+    fn test_get_move_with_time_limit() {
+        let position = Position::new();
+        let hash_table = HashTable::new(10);
+        let mut search = MidgameSearch::new(position, &hash_table);
+
+        let limits = SearchLimits {
+            time: Some(Duration::from_millis(50)),
+            depth: Some(60),
+            ..Default::default()
+        };
+
+        let start = Instant::now();
+        let best_move = search.search(&limits, &mut |_: SearchEvent| {}).best_move;
+
+        assert!(position.is_valid_move(best_move));
+        assert!(search.aborted);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_search_observer() {
+        let position = Position::new_xot();
+        let hash_table = HashTable::new(16);
+        let mut search = MidgameSearch::new(position, &hash_table);
+
+        let limits = SearchLimits {
+            depth: Some(3),
+            ..Default::default()
+        };
+
+        let mut root_moves = vec![];
+        let mut depths = vec![];
+        let result = search.search(&limits, &mut |event: SearchEvent| match event {
+            SearchEvent::RootMove { depth, root_move } => root_moves.push((depth, root_move)),
+            SearchEvent::DepthCompleted(result) => depths.push(result.depth),
+        });
+
+        let n_children = position.children().len();
+        assert_eq!(depths, vec![1, 2, 3]);
+        assert_eq!(root_moves.len(), 3 * n_children);
+        assert_eq!(
+            &result.root_moves[..],
+            &root_moves[2 * n_children..]
+                .iter()
+                .map(|(_, root_move)| *root_move)
+                .collect::<Vec<_>>()[..]
+        );
+    }
+
+    #[test]
+    fn test_stop_flag() {
+        let position = Position::new();
+        let hash_table = HashTable::new(10);
+        let mut search = MidgameSearch::new(position, &hash_table);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let limits = SearchLimits {
+            depth: Some(60),
+            stop: Some(stop.clone()),
+            ..Default::default()
+        };
+
+        // Stop the search as soon as the first iteration completes
+        let result = search.search(&limits, &mut |event: SearchEvent| {
+            if let SearchEvent::DepthCompleted(_) = event {
+                stop.store(true, Ordering::Relaxed);
+            }
+        });
+
+        assert!(search.aborted);
+        assert!(position.is_valid_move(result.best_move));
+        assert!(result.depth < 60);
+    }
+}
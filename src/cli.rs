@@ -0,0 +1,844 @@
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Write};
+#[cfg(feature = "server")]
+use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use argh::FromArgs;
+
+use swap::bot::book::builder::{BookBuilder, BuildOptions};
+#[cfg(feature = "server")]
+use swap::bot::book::load_book;
+use swap::bot::book::{set_book_path, Book};
+use swap::bot::edax::bot::{search_parallel, MIDGAME_DEPTH};
+use swap::bot::edax::endgame::EndgameSearch;
+use swap::bot::edax::midgame::MidgameSearch;
+use swap::bot::edax::train::{exact_score, game_samples, parse_samples, train, TrainOptions};
+#[cfg(feature = "server")]
+use swap::bot::edax::weights::set_eval_path;
+use swap::bot::edax::weights::{load_eval, load_weights, save_eval};
+use swap::bot::hash_table::{HashTable, DEFAULT_HASH_TABLE_BITS};
+use swap::bot::{
+    format_nodes, format_score, new_bot_with, Bot, BotOptions, SearchEvent, SearchLimits,
+    Selectivity,
+};
+#[cfg(feature = "server")]
+use swap::frontend::app::{run_app, DEFAULT_ASSETS_DIR};
+use swap::othello::backend::{set_backend, Backend};
+use swap::othello::ffo_problems::parse_ffo_problems;
+use swap::othello::perft::{
+    move_generators, perft_with, position_generator, MoveGenerator, START_PERFT,
+};
+use swap::othello::position::{Position, XOT_POSITIONS};
+use swap::othello::squares::square_name;
+
+/// Othello engine with a web interface. Without a subcommand, serves the web interface.
+#[derive(FromArgs)]
+pub struct Args {
+    #[argh(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    #[cfg(feature = "server")]
+    Serve(ServeCommand),
+    Solve(SolveCommand),
+    Analyze(AnalyzeCommand),
+    Book(BookCommand),
+    Perft(PerftCommand),
+    Bench(BenchCommand),
+    Selfplay(SelfplayCommand),
+    Train(TrainCommand),
+    Ffo(FfoCommand),
+}
+
+/// Serve the web interface
+#[cfg(feature = "server")]
+#[derive(FromArgs)]
+#[argh(subcommand, name = "serve")]
+struct ServeCommand {
+    /// host name or IP address to listen on
+    #[argh(option, default = "ServeCommand::default().host")]
+    host: String,
+
+    /// port to listen on
+    #[argh(option, default = "ServeCommand::default().port")]
+    port: u16,
+
+    /// directory of the web interface files
+    #[argh(option, default = "ServeCommand::default().assets")]
+    assets: PathBuf,
+
+    /// evaluation weights of the edax bot in the format of Edax, by default the file of
+    /// the SWAP_EVAL environment variable or else eval.dat
+    #[argh(option)]
+    eval: Option<PathBuf>,
+
+    /// opening book of the edax bot, by default the file of the SWAP_BOOK environment
+    /// variable or else book.dat if it exists
+    #[argh(option)]
+    book: Option<PathBuf>,
+
+    /// search threads of the edax bot in each game
+    #[argh(option, default = "ServeCommand::default().threads")]
+    threads: usize,
+}
+
+#[cfg(feature = "server")]
+impl Default for ServeCommand {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 3000,
+            assets: PathBuf::from(DEFAULT_ASSETS_DIR),
+            eval: None,
+            book: None,
+            threads: BotOptions::default().threads,
+        }
+    }
+}
+
+/// Solve a position to the end of the game and print the score of every move
+#[derive(FromArgs)]
+#[argh(subcommand, name = "solve")]
+struct SolveCommand {
+    /// board such as "--XO--(64 squares from a1 to h8)--OX X" with the color to move
+    /// last, or transcript of moves from the start position such as "f5d6c3"
+    #[argh(positional)]
+    position: Position,
+
+    /// search threads sharing the hash table
+    #[argh(option, default = "1")]
+    threads: usize,
+}
+
+/// Search a position to a fixed depth and print the score of every move
+#[derive(FromArgs)]
+#[argh(subcommand, name = "analyze")]
+struct AnalyzeCommand {
+    /// board such as "--XO--(64 squares from a1 to h8)--OX X" with the color to move
+    /// last, or transcript of moves from the start position such as "f5d6c3"
+    #[argh(positional)]
+    position: Position,
+
+    /// search depth
+    #[argh(option, default = "MIDGAME_DEPTH")]
+    depth: u32,
+
+    /// probability in percent that pruning doesn't change the result: 73, 87, 95, 98,
+    /// 99 or 100, the edax bot's default if not set
+    #[argh(option)]
+    selectivity: Option<u32>,
+
+    /// search threads sharing the hash table
+    #[argh(option, default = "1")]
+    threads: usize,
+}
+
+/// Create or grow an opening book
+#[derive(FromArgs)]
+#[argh(subcommand, name = "book")]
+struct BookCommand {
+    /// book file, loaded if it exists and saved after every batch of changes
+    #[argh(positional)]
+    path: PathBuf,
+
+    /// depth of the searches scoring moves that leave the book
+    #[argh(option, default = "BuildOptions::default().depth")]
+    depth: u32,
+
+    /// positions with fewer empty squares are not added
+    #[argh(option, default = "BuildOptions::default().min_empties")]
+    min_empties: u32,
+
+    /// only expand leaves scoring at most this many discs below the best move
+    #[argh(option, default = "BuildOptions::default().max_error")]
+    max_error: i32,
+
+    /// add the start positions of the XOT openings
+    #[argh(switch)]
+    xot: bool,
+
+    /// add the games of a file with one transcript per line, such as "f5d6c3d3c4"
+    #[argh(option)]
+    import: Option<PathBuf>,
+
+    /// number of leaves to expand
+    #[argh(option, default = "0")]
+    expand: usize,
+}
+
+/// Count the positions reached from the start position, to validate move generation
+#[derive(FromArgs)]
+#[argh(subcommand, name = "perft")]
+struct PerftCommand {
+    /// number of moves, passes included
+    #[argh(option, default = "10")]
+    depth: u32,
+
+    /// move generator to use, such as "simple/edax_slow", instead of the one of the
+    /// fastest backend
+    #[argh(option)]
+    generator: Option<String>,
+
+    /// run all move generators and check that they agree
+    #[argh(switch)]
+    compare: bool,
+}
+
+/// Search XOT openings to a fixed depth and report the search speed
+#[derive(FromArgs)]
+#[argh(subcommand, name = "bench")]
+struct BenchCommand {
+    /// search depth
+    #[argh(option, default = "MIDGAME_DEPTH")]
+    depth: u32,
+
+    /// number of XOT openings to search
+    #[argh(option, default = "20")]
+    openings: usize,
+}
+
+/// Play games between two bots, starting from each XOT opening once with each color
+#[derive(FromArgs)]
+#[argh(subcommand, name = "selfplay")]
+struct SelfplayCommand {
+    /// first bot: random, squared or edax
+    #[argh(option, default = "String::from(\"edax\")")]
+    first: String,
+
+    /// second bot: random, squared or edax
+    #[argh(option, default = "String::from(\"squared\")")]
+    second: String,
+
+    /// number of XOT openings to play
+    #[argh(option, default = "10")]
+    openings: usize,
+
+    /// maximum midgame search depth, the bots' default if not set
+    #[argh(option)]
+    depth: Option<u32>,
+
+    /// time per move in milliseconds, unlimited if not set
+    #[argh(option)]
+    time: Option<u64>,
+
+    /// opening book of the edax bot, by default the file of the SWAP_BOOK environment
+    /// variable or else book.dat if it exists
+    #[argh(option)]
+    book: Option<PathBuf>,
+
+    /// maximum number of discs a book move may score less than the best one, so the
+    /// games out of the book differ
+    #[argh(option, default = "0")]
+    book_randomness: i32,
+
+    /// search threads of the edax bot
+    #[argh(option, default = "1")]
+    threads: usize,
+
+    /// append the positions of the games with their final scores to this file, to
+    /// train the evaluation on
+    #[argh(option)]
+    record: Option<PathBuf>,
+
+    /// recorded positions with at most this many empty squares are scored by solving
+    /// them instead of with the game result
+    #[argh(option, default = "0")]
+    exact: u32,
+}
+
+/// Fit the evaluation weights to positions with target scores and save them as an
+/// evaluation file of Edax
+#[derive(FromArgs)]
+#[argh(subcommand, name = "train")]
+struct TrainCommand {
+    /// files with a position and its score for the player to move on each line, such as
+    /// those written by selfplay --record
+    #[argh(positional)]
+    samples: Vec<PathBuf>,
+
+    /// evaluation file to write
+    #[argh(option, default = "PathBuf::from(\"trained.dat\")")]
+    output: PathBuf,
+
+    /// evaluation file to start from, zero weights if not set
+    #[argh(option)]
+    initial: Option<PathBuf>,
+
+    /// passes of gradient descent over the positions of each ply
+    #[argh(option, default = "TrainOptions::default().epochs")]
+    epochs: usize,
+
+    /// fraction of the error corrected on each pass
+    #[argh(option, default = "TrainOptions::default().learning_rate")]
+    learning_rate: f32,
+
+    /// percentage of the positions kept out of training to measure the validation error
+    #[argh(option, default = "TrainOptions::default().validation_percent")]
+    validation: u32,
+}
+
+/// Solve FFO endgame problems and report the search speed
+#[derive(FromArgs)]
+#[argh(subcommand, name = "ffo")]
+struct FfoCommand {
+    /// number of the first problem
+    #[argh(option, default = "40")]
+    first: usize,
+
+    /// number of the last problem
+    #[argh(option, default = "44")]
+    last: usize,
+
+    /// move generation backend: scalar, bmi2 or avx2, the fastest one by default
+    #[argh(option)]
+    backend: Option<String>,
+
+    /// solve the problems with every backend the CPU supports
+    #[argh(switch)]
+    compare: bool,
+}
+
+/// Number of leaves expanded between saves of the book
+const EXPAND_BATCH_SIZE: usize = 100;
+
+pub fn run(args: Args) -> io::Result<()> {
+    match args.command {
+        #[cfg(feature = "server")]
+        None => run_serve(ServeCommand::default()),
+        #[cfg(not(feature = "server"))]
+        None => Err(io::Error::other(
+            "Built without the server feature, choose a subcommand",
+        )),
+        #[cfg(feature = "server")]
+        Some(Command::Serve(command)) => run_serve(command),
+        Some(Command::Solve(command)) => run_solve(command),
+        Some(Command::Analyze(command)) => run_analyze(command),
+        Some(Command::Book(command)) => run_book(command),
+        Some(Command::Perft(command)) => run_perft(command),
+        Some(Command::Bench(command)) => run_bench(command),
+        Some(Command::Selfplay(command)) => run_selfplay(command),
+        Some(Command::Train(command)) => run_train(command),
+        Some(Command::Ffo(command)) => run_ffo(command),
+    }
+}
+
+#[cfg(feature = "server")]
+fn run_serve(command: ServeCommand) -> io::Result<()> {
+    if let Some(path) = command.eval {
+        set_eval_path(path).map_err(io::Error::other)?;
+    }
+
+    if let Some(path) = command.book {
+        set_book_path(path).map_err(io::Error::other)?;
+    }
+
+    // Load the weights and book now, so a missing file is reported before anyone plays
+    if let Err(error) = load_weights().and(load_book()) {
+        eprintln!("{}, the edax bot is unavailable", error);
+    }
+
+    let addr = (command.host.as_str(), command.port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::other(format!("No address found for {}", command.host)))?;
+
+    let bot_options = BotOptions {
+        threads: command.threads,
+        ..Default::default()
+    };
+    tokio::runtime::Runtime::new()?.block_on(run_app(addr, &command.assets, bot_options))
+}
+
+/// Returns `position` after passing if the player to move has no moves,
+/// or an error if the game is over
+fn playable(mut position: Position) -> io::Result<Position> {
+    if !position.has_moves() {
+        position.pass();
+
+        if !position.has_moves() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The game is over",
+            ));
+        }
+        println!("Passed, the player to move has no valid moves");
+    }
+    Ok(position)
+}
+
+/// Prints a line for each iteration of a search
+fn print_iteration(event: SearchEvent) {
+    if let SearchEvent::DepthCompleted(result) = event {
+        println!(
+            "Depth {:2} @ {:>4}: {} score {} | {} / {:.3}s",
+            result.depth,
+            result.selectivity,
+            square_name(result.best_move),
+            format_score(result.score),
+            format_nodes(result.nodes),
+            result.duration.as_secs_f64(),
+        );
+    }
+}
+
+fn run_solve(command: SolveCommand) -> io::Result<()> {
+    load_weights().map_err(io::Error::other)?;
+    let position = playable(command.position)?;
+    println!("{}", position);
+
+    let hash_table = HashTable::new(DEFAULT_HASH_TABLE_BITS);
+    let result = search_parallel(
+        command.threads,
+        &SearchLimits::default(),
+        &mut print_iteration,
+        |thread_index, limits, observer| {
+            let mut search = EndgameSearch::new(position, &hash_table);
+            search.set_thread_index(thread_index);
+            search.search(limits, observer)
+        },
+    );
+
+    println!("{}", result);
+    Ok(())
+}
+
+fn run_analyze(command: AnalyzeCommand) -> io::Result<()> {
+    let selectivity = command
+        .selectivity
+        .map(|percentage| {
+            Selectivity::from_percentage(percentage).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Unknown selectivity {}", percentage),
+                )
+            })
+        })
+        .transpose()?;
+
+    load_weights().map_err(io::Error::other)?;
+    let position = playable(command.position)?;
+    println!("{}", position);
+
+    let limits = SearchLimits {
+        depth: Some(command.depth),
+        selectivity,
+        ..Default::default()
+    };
+
+    let hash_table = HashTable::new(DEFAULT_HASH_TABLE_BITS);
+    let result = search_parallel(
+        command.threads,
+        &limits,
+        &mut print_iteration,
+        |thread_index, limits, observer| {
+            let mut search = MidgameSearch::new(position, &hash_table);
+            search.set_thread_index(thread_index);
+            search.search(limits, observer)
+        },
+    );
+
+    println!("{}", result);
+    Ok(())
+}
+
+fn run_book(command: BookCommand) -> io::Result<()> {
+    let book = match Book::load(&command.path) {
+        Ok(book) => book,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Book::new(),
+        Err(error) => return Err(error),
+    };
+    println!("Loaded {} positions", book.len());
+
+    let options = BuildOptions {
+        depth: command.depth,
+        min_empties: command.min_empties,
+        max_error: command.max_error,
+    };
+    let mut builder = BookBuilder::new(book, options);
+
+    builder.add_root(&Position::new());
+
+    if command.xot {
+        for position in XOT_POSITIONS.iter() {
+            builder.add_root(position);
+        }
+    }
+
+    if let Some(path) = &command.import {
+        let added = builder.import_games(&std::fs::read_to_string(path)?)?;
+        println!("Imported {} positions", added);
+    }
+
+    builder.negamax();
+    builder.book().save(&command.path)?;
+
+    let mut expanded = 0;
+    while expanded < command.expand {
+        let batch = EXPAND_BATCH_SIZE.min(command.expand - expanded);
+        let added = builder.expand(batch);
+        builder.book().save(&command.path)?;
+
+        expanded += added;
+        println!(
+            "Expanded {} leaves, book has {} positions",
+            expanded,
+            builder.book().len()
+        );
+
+        if added < batch {
+            println!("No leaves left to expand");
+            break;
+        }
+    }
+
+    println!("Saved {} positions", builder.book().len());
+    Ok(())
+}
+
+/// Runs perft with `generator`, returns false if the count is not the known one
+fn print_perft(generator: &MoveGenerator, depth: u32) -> bool {
+    let start = Instant::now();
+    let nodes = perft_with(&Position::new(), depth, generator);
+    let seconds = start.elapsed().as_secs_f64();
+
+    let expected = START_PERFT.get(depth as usize).copied();
+    let verdict = match expected {
+        Some(expected) if expected == nodes => "ok",
+        Some(_) => "WRONG",
+        None => "unknown",
+    };
+
+    println!(
+        "{:<20} depth {:>2} {:>15} nodes {:>8.3}s {:>8.1} Mnodes/s  {}",
+        generator.name,
+        depth,
+        nodes,
+        seconds,
+        nodes as f64 / seconds / 1e6,
+        verdict
+    );
+
+    expected.is_none_or(|expected| expected == nodes)
+}
+
+fn run_perft(command: PerftCommand) -> io::Result<()> {
+    let generators = move_generators();
+
+    let selected: Vec<_> = if command.compare {
+        generators
+    } else if let Some(name) = &command.generator {
+        let names: Vec<_> = generators.iter().map(|g| g.name.clone()).collect();
+        let generator = generators
+            .into_iter()
+            .find(|generator| &generator.name == name)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Unknown generator, choose from: {}", names.join(", ")),
+                )
+            })?;
+        vec![generator]
+    } else {
+        vec![position_generator()]
+    };
+
+    let mut all_correct = true;
+    for generator in &selected {
+        if command.compare {
+            all_correct &= print_perft(generator, command.depth);
+        } else {
+            for depth in 1..=command.depth {
+                all_correct &= print_perft(generator, depth);
+            }
+        }
+    }
+
+    if !all_correct {
+        return Err(io::Error::other(
+            "Perft counts differ from the known counts",
+        ));
+    }
+    Ok(())
+}
+
+fn run_bench(command: BenchCommand) -> io::Result<()> {
+    let limits = SearchLimits {
+        depth: Some(command.depth),
+        ..Default::default()
+    };
+
+    // Load the weights first, so loading doesn't count as search time
+    load_weights().map_err(io::Error::other)?;
+
+    let mut hash_table = HashTable::new(DEFAULT_HASH_TABLE_BITS);
+    let mut total_nodes = 0;
+    let mut total_seconds = 0.0;
+
+    for (i, position) in XOT_POSITIONS.iter().take(command.openings).enumerate() {
+        // Each search starts from an empty table, so runs are comparable
+        hash_table.clear();
+        let mut search = MidgameSearch::new(*position, &hash_table);
+        let result = search.search(&limits, &mut |_: SearchEvent| {});
+
+        let seconds = result.duration.as_secs_f64();
+        println!(
+            "XOT #{:3}, depth {:2}: {} score {} | {} / {:.3}s / {:.1} Mnodes/s",
+            i + 1,
+            result.depth,
+            square_name(result.best_move),
+            format_score(result.score),
+            format_nodes(result.nodes),
+            seconds,
+            result.nodes as f64 / seconds / 1e6,
+        );
+
+        total_nodes += result.nodes;
+        total_seconds += seconds;
+    }
+
+    println!(
+        "Total: {} / {:.3}s / {:.1} Mnodes/s",
+        format_nodes(total_nodes),
+        total_seconds,
+        total_nodes as f64 / total_seconds / 1e6,
+    );
+    Ok(())
+}
+
+/// Plays a game from `start`, where `bots[0]` moves first. Returns the final disc
+/// difference for `bots[0]` and the moves played, without passes.
+fn play_game(
+    start: Position,
+    bots: [&mut dyn Bot; 2],
+    limits: &SearchLimits,
+) -> (isize, Vec<usize>) {
+    let mut position = start;
+    let mut turn = 0;
+    let mut moves = Vec::new();
+
+    loop {
+        if !position.has_moves() {
+            position.pass();
+            turn = 1 - turn;
+
+            if !position.has_moves() {
+                break;
+            }
+        }
+
+        let move_ = bots[turn].get_move(&position, limits);
+        position.do_move(move_);
+        moves.push(move_);
+        turn = 1 - turn;
+    }
+
+    let score = position.final_score();
+    (if turn == 0 { score } else { -score }, moves)
+}
+
+fn run_selfplay(command: SelfplayCommand) -> io::Result<()> {
+    if let Some(path) = command.book {
+        set_book_path(path).map_err(io::Error::other)?;
+    }
+
+    let options = BotOptions {
+        threads: command.threads,
+        book_randomness: command.book_randomness,
+    };
+    let mut first = new_bot_with(&command.first, &options).map_err(io::Error::other)?;
+    let mut second = new_bot_with(&command.second, &options).map_err(io::Error::other)?;
+
+    let limits = SearchLimits {
+        depth: command.depth,
+        time: command.time.map(Duration::from_millis),
+        ..Default::default()
+    };
+
+    if command.record.is_some() && command.exact > 0 {
+        load_weights().map_err(io::Error::other)?;
+    }
+    let mut record = match &command.record {
+        Some(path) => Some(BufWriter::new(
+            OpenOptions::new().create(true).append(true).open(path)?,
+        )),
+        None => None,
+    };
+    let hash_table = HashTable::new(DEFAULT_HASH_TABLE_BITS);
+
+    let (mut wins, mut draws, mut losses) = (0, 0, 0);
+    let mut total_score = 0;
+
+    for (i, opening) in XOT_POSITIONS.iter().take(command.openings).enumerate() {
+        for first_moves_first in [true, false] {
+            let (score, moves) = if first_moves_first {
+                play_game(*opening, [first.as_mut(), second.as_mut()], &limits)
+            } else {
+                let (score, moves) =
+                    play_game(*opening, [second.as_mut(), first.as_mut()], &limits);
+                (-score, moves)
+            };
+
+            match score.signum() {
+                1 => wins += 1,
+                0 => draws += 1,
+                _ => losses += 1,
+            }
+            total_score += score;
+
+            if let Some(record) = &mut record {
+                for mut sample in game_samples(opening, &moves) {
+                    if sample.position.count_empty() <= command.exact {
+                        sample.score = exact_score(&sample.position, &hash_table);
+                    }
+                    writeln!(record, "{}", sample)?;
+                }
+            }
+
+            let transcript: String = moves.iter().map(|&move_| square_name(move_)).collect();
+            println!(
+                "XOT #{:3}, {} moves {}: {} {:+3} | {}",
+                i + 1,
+                command.first,
+                if first_moves_first { "first" } else { "second" },
+                command.first,
+                score,
+                transcript,
+            );
+        }
+    }
+
+    println!(
+        "{} against {}: {} wins, {} draws, {} losses, {:+} discs",
+        command.first, command.second, wins, draws, losses, total_score,
+    );
+    if let Some(record) = &mut record {
+        record.flush()?;
+    }
+    Ok(())
+}
+
+fn run_train(command: TrainCommand) -> io::Result<()> {
+    let mut samples = Vec::new();
+    for path in &command.samples {
+        samples.extend(parse_samples(&std::fs::read_to_string(path)?)?);
+    }
+    println!("Loaded {} positions", samples.len());
+
+    let initial = command.initial.as_deref().map(load_eval).transpose()?;
+    let options = TrainOptions {
+        epochs: command.epochs,
+        learning_rate: command.learning_rate,
+        validation_percent: command.validation,
+    };
+
+    let start = Instant::now();
+    let (eval, errors) = train(&samples, initial.as_ref(), &options);
+
+    let format_error = |error: Option<f64>| match error {
+        Some(error) => format!("{:6.2}", error),
+        None => format!("{:>6}", "-"),
+    };
+    println!("Ply | Training       error | Validation     error");
+    for error in errors
+        .iter()
+        .filter(|error| error.n_train + error.n_validation > 0)
+    {
+        println!(
+            "{:3} | {:8} {} | {:10} {}",
+            error.ply,
+            error.n_train,
+            format_error(error.train_error),
+            error.n_validation,
+            format_error(error.validation_error),
+        );
+    }
+
+    save_eval(&command.output, &eval)?;
+    println!(
+        "Saved {} in {:.1}s",
+        command.output.display(),
+        start.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+/// Solves the FFO problems `first` to `last` with `backend`,
+/// returns false if a score is wrong
+fn solve_ffo(backend: Backend, first: usize, last: usize) -> io::Result<bool> {
+    set_backend(backend).map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+    println!("Backend {}", backend);
+
+    load_weights().map_err(io::Error::other)?;
+
+    let hash_table = HashTable::new(22);
+    let mut all_correct = true;
+    let mut total_nodes = 0;
+    let mut total_seconds = 0.0;
+
+    for problem in parse_ffo_problems()
+        .iter()
+        .filter(|p| (first..=last).contains(&p.line_number))
+    {
+        let mut search = EndgameSearch::new(problem.position, &hash_table);
+        let result = search.search(&SearchLimits::default(), &mut |_: SearchEvent| {});
+
+        let seconds = result.duration.as_secs_f64();
+        let best_score = *problem.solutions.values().max().unwrap();
+        let is_correct = result.score == best_score;
+
+        println!(
+            "FFO #{:2}, depth {:2}: score {:3} | {} / {:.3}s / {:.1} Mnodes/s{}",
+            problem.line_number,
+            problem.depth,
+            result.score,
+            format_nodes(result.nodes),
+            seconds,
+            result.nodes as f64 / seconds / 1e6,
+            if is_correct { "" } else { "  WRONG" },
+        );
+
+        all_correct &= is_correct;
+        total_nodes += result.nodes;
+        total_seconds += seconds;
+    }
+
+    println!(
+        "Total: {} / {:.3}s / {:.1} Mnodes/s",
+        format_nodes(total_nodes),
+        total_seconds,
+        total_nodes as f64 / total_seconds / 1e6,
+    );
+
+    Ok(all_correct)
+}
+
+fn run_ffo(command: FfoCommand) -> io::Result<()> {
+    let backends = if command.compare {
+        Backend::supported()
+    } else if let Some(name) = &command.backend {
+        let backend = Backend::from_name(name).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unknown backend {}", name),
+            )
+        })?;
+        vec![backend]
+    } else {
+        vec![Backend::best()]
+    };
+
+    let mut all_correct = true;
+    for backend in backends {
+        all_correct &= solve_ffo(backend, command.first, command.last)?;
+    }
+
+    if !all_correct {
+        return Err(io::Error::other("Some scores are wrong"));
+    }
+    Ok(())
+}
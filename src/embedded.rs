@@ -0,0 +1,48 @@
+//! Data files compiled into the binary with the `embedded-data` feature. The build script
+//! compresses them, they are decompressed by the first caller, which keeps the result.
+
+use std::io::Read;
+
+use flate2::read::GzDecoder;
+
+static EVAL: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/eval.dat.gz"));
+static XOT: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/xot.json.gz"));
+static FFO_PROBLEMS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/ffo_problems.txt.gz"));
+
+fn decompress(data: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    GzDecoder::new(data)
+        .read_to_end(&mut bytes)
+        .expect("Embedded data is corrupted");
+    bytes
+}
+
+/// Returns the contents of `eval.dat`
+pub fn eval() -> Vec<u8> {
+    decompress(EVAL)
+}
+
+/// Returns the contents of `assets/xot.json`
+pub fn xot() -> String {
+    String::from_utf8(decompress(XOT)).expect("Embedded XOT file is not UTF-8")
+}
+
+/// Returns the contents of `assets/ffo_problems.txt`
+pub fn ffo_problems() -> String {
+    String::from_utf8(decompress(FFO_PROBLEMS)).expect("Embedded FFO file is not UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_data() {
+        assert_eq!(eval(), std::fs::read("eval.dat").unwrap());
+        assert_eq!(xot(), std::fs::read_to_string("assets/xot.json").unwrap());
+        assert_eq!(
+            ffo_problems(),
+            std::fs::read_to_string("assets/ffo_problems.txt").unwrap()
+        );
+    }
+}
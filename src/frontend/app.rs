@@ -1,23 +1,42 @@
-use axum::{extract::ws::WebSocketUpgrade, response::IntoResponse, routing::get, Router};
-use std::net::SocketAddr;
+use axum::extract::{ws::WebSocketUpgrade, State};
+use axum::{response::IntoResponse, routing::get, Router};
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
 use tower_http::services::ServeDir;
 
 use super::websocket::handle_socket;
+use crate::bot::BotOptions;
 
-pub async fn run_app() {
-    let app = Router::new()
+/// Directory of the web interface files when running from the repository
+pub const DEFAULT_ASSETS_DIR: &str = "assets";
+
+/// Creates the routes of the websocket at `/ws` and of the files in `assets`. The bots
+/// of every game use `bot_options`.
+pub fn create_router(assets: &Path, bot_options: BotOptions) -> Router {
+    Router::new()
         .route("/ws", get(ws_handler))
-        .nest_service("/", ServeDir::new("assets"));
+        .nest_service("/", ServeDir::new(assets))
+        .with_state(bot_options)
+}
+
+/// Serves the web interface at `addr` until the server fails
+pub async fn run_app(addr: SocketAddr, assets: &Path, bot_options: BotOptions) -> io::Result<()> {
+    let app = create_router(assets, bot_options);
 
-    let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
+    let listener = TcpListener::bind(addr)?;
     println!("Listening on {}", addr);
 
-    axum::Server::bind(&addr)
+    axum::Server::from_tcp(listener)
+        .map_err(io::Error::other)?
         .serve(app.into_make_service())
         .await
-        .unwrap();
+        .map_err(io::Error::other)
 }
 
-async fn ws_handler(ws: WebSocketUpgrade) -> impl IntoResponse {
-    ws.on_upgrade(handle_socket)
+async fn ws_handler(
+    State(bot_options): State<BotOptions>,
+    ws: WebSocketUpgrade,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| handle_socket(socket, bot_options))
 }
@@ -1,14 +1,20 @@
 use std::fmt::{self, Display};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
+use crate::bot::edax::eval::{explain, Explanation};
+use crate::bot::{available_bots, BotOptions, SearchEvent, SearchLimits, SearchResult};
 use crate::othello::board::{Board, BLACK, WHITE};
 use crate::othello::game::Game;
+use crate::othello::squares::square_name;
 use axum::extract::ws::{Message, WebSocket};
 use futures::{
     stream::{SplitSink, SplitStream},
     SinkExt, StreamExt,
 };
-use serde_json::Value;
+use serde_json::{json, Value};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 
 enum HandlerError {
     WebSocketError(axum::Error),
@@ -21,7 +27,6 @@ enum HandlerError {
     UnknownCommand((String, String)),
 }
 
-use tokio::time::sleep;
 use HandlerError::*;
 
 impl Display for HandlerError {
@@ -47,33 +52,86 @@ impl Display for HandlerError {
     }
 }
 
+/// Maximum time a bot may think about a move
+const BOT_TIME_LIMIT: Duration = Duration::from_secs(5);
+
+/// Pause before a bot searches, so the moves of games between bots can be followed
+const BOT_MOVE_DELAY: Duration = Duration::from_millis(100);
+
+/// Message sent by a bot searching on a blocking worker thread
+enum BotMessage {
+    Progress(u64, SearchResult),
+    Done(u64, SearchResult),
+}
+
+/// Bot search that is running on a blocking worker thread
+struct RunningSearch {
+    id: u64,
+    stop: Arc<AtomicBool>,
+}
+
+enum SessionEvent {
+    WebSocket(Option<Result<Message, axum::Error>>),
+    Bot(BotMessage),
+}
+
 struct GameSession {
     ws_sender: SplitSink<WebSocket, Message>,
     ws_receiver: SplitStream<WebSocket>,
     game: Game,
+    bot_sender: UnboundedSender<BotMessage>,
+    bot_receiver: UnboundedReceiver<BotMessage>,
+    search: Option<RunningSearch>,
+    next_search_id: u64,
 }
 
 impl GameSession {
-    fn new(ws_sender: SplitSink<WebSocket, Message>, ws_receiver: SplitStream<WebSocket>) -> Self {
+    fn new(
+        ws_sender: SplitSink<WebSocket, Message>,
+        ws_receiver: SplitStream<WebSocket>,
+        bot_options: BotOptions,
+    ) -> Self {
+        let (bot_sender, bot_receiver) = unbounded_channel();
+
         Self {
             ws_sender,
             ws_receiver,
-            game: Game::new(),
+            game: Game::with_bot_options(bot_options),
+            bot_sender,
+            bot_receiver,
+            search: None,
+            next_search_id: 0,
         }
     }
 
     async fn run(&mut self) -> Result<(), axum::Error> {
         self.send_current_board().await?;
+        self.ws_sender.send(Message::Text(bots_message())).await?;
+
+        loop {
+            let event = tokio::select! {
+                msg = self.ws_receiver.next() => SessionEvent::WebSocket(msg),
+                Some(msg) = self.bot_receiver.recv() => SessionEvent::Bot(msg),
+            };
 
-        while let Some(msg) = self.ws_receiver.next().await {
-            if let Err(e) = self.handle_message(msg).await {
+            let result = match event {
+                SessionEvent::WebSocket(None) => break,
+                SessionEvent::WebSocket(Some(msg)) => self.handle_message(msg).await,
+                SessionEvent::Bot(msg) => self.handle_bot_message(msg).await,
+            };
+
+            if let Err(e) = result {
                 match e {
-                    WebSocketError(e) => return Err(e),
+                    WebSocketError(e) => {
+                        self.cancel_search();
+                        return Err(e);
+                    }
                     _ => eprintln!("{}", e),
                 }
             }
         }
 
+        self.cancel_search();
         Ok(())
     }
 
@@ -103,6 +161,7 @@ impl GameSession {
         let (command, data) = object.iter().next().ok_or(MissingKeyJson(text))?;
 
         match (command.as_str(), data) {
+            ("stop", data) => self.handle_stop((command, data)).await,
             ("undo", data) => self.handle_undo((command, data)).await,
             ("redo", data) => self.handle_redo((command, data)).await,
             ("human_move", data) => self.handle_human_move((command, data)).await,
@@ -110,23 +169,35 @@ impl GameSession {
             ("xot_game", data) => self.handle_xot_game((command, data)).await,
             ("set_black_player", data) => self.handle_set_black_player((command, data)).await,
             ("set_white_player", data) => self.handle_set_white_player((command, data)).await,
+            ("explain", data) => self.handle_explain((command, data)).await,
             _ => Err(UnknownCommand((command.clone(), data.to_string()))),
         }
     }
 
+    async fn handle_stop(&mut self, _: (&String, &Value)) -> Result<(), HandlerError> {
+        self.cancel_search();
+        Ok(())
+    }
+
     async fn handle_undo(&mut self, _: (&String, &Value)) -> Result<(), HandlerError> {
+        self.cancel_search();
+
         if self.game.undo() {
             self.send_current_board().await.map_err(WebSocketError)?;
         }
 
+        self.start_bot_search();
         Ok(())
     }
 
     async fn handle_redo(&mut self, _: (&String, &Value)) -> Result<(), HandlerError> {
+        self.cancel_search();
+
         if self.game.redo() {
             self.send_current_board().await.map_err(WebSocketError)?;
         }
 
+        self.start_bot_search();
         Ok(())
     }
 
@@ -151,75 +222,347 @@ impl GameSession {
             ));
         }
 
+        self.cancel_search();
         self.game.do_move(index);
 
         self.send_current_board().await.map_err(WebSocketError)?;
-        self.do_bot_move().await
+        self.start_bot_search();
+        Ok(())
     }
 
     async fn handle_new_game(&mut self, _: (&String, &Value)) -> Result<(), HandlerError> {
+        self.cancel_search();
         self.game.reset(Board::new());
         self.send_current_board().await.map_err(WebSocketError)?;
-        self.do_bot_move().await
+        self.start_bot_search();
+        Ok(())
     }
 
     async fn handle_xot_game(&mut self, _: (&String, &Value)) -> Result<(), HandlerError> {
+        self.cancel_search();
         self.game.reset(Board::new_xot());
         self.send_current_board().await.map_err(WebSocketError)?;
-        self.do_bot_move().await
+        self.start_bot_search();
+        Ok(())
     }
 
     async fn handle_set_black_player(
         &mut self,
         args: (&String, &Value),
     ) -> Result<(), HandlerError> {
-        let bot_name = args.1.as_str().unwrap();
-        self.game.set_player(BLACK, bot_name);
-        self.do_bot_move().await
+        let bot_name = args.1.as_str().ok_or_else(|| {
+            HandlerValueError(
+                (args.0.clone(), args.1.to_string()),
+                "bot name is not a string".to_string(),
+            )
+        })?;
+        self.cancel_search();
+        let result = self.game.set_player(BLACK, bot_name);
+        self.start_bot_search();
+        result.map_err(|error| HandlerValueError((args.0.clone(), args.1.to_string()), error))
     }
 
     async fn handle_set_white_player(
         &mut self,
         args: (&String, &Value),
     ) -> Result<(), HandlerError> {
-        let bot_name = args.1.as_str().unwrap();
-        self.game.set_player(WHITE, bot_name);
-        self.do_bot_move().await
+        let bot_name = args.1.as_str().ok_or_else(|| {
+            HandlerValueError(
+                (args.0.clone(), args.1.to_string()),
+                "bot name is not a string".to_string(),
+            )
+        })?;
+        self.cancel_search();
+        let result = self.game.set_player(WHITE, bot_name);
+        self.start_bot_search();
+        result.map_err(|error| HandlerValueError((args.0.clone(), args.1.to_string()), error))
     }
 
-    async fn do_bot_move(&mut self) -> Result<(), HandlerError> {
-        loop {
-            let board = *self.current_board();
+    async fn handle_bot_message(&mut self, msg: BotMessage) -> Result<(), HandlerError> {
+        let (search_id, result, is_done) = match msg {
+            BotMessage::Progress(search_id, result) => (search_id, result, false),
+            BotMessage::Done(search_id, result) => (search_id, result, true),
+        };
 
-            let Some(bot) = self.game.get_current_bot() else {
-                return Ok(());
-            };
+        // Discard results of searches that were cancelled
+        if self.search.as_ref().map(|search| search.id) != Some(search_id) {
+            return Ok(());
+        }
+
+        if !is_done {
+            let message = thinking_message(&result);
+            return self
+                .ws_sender
+                .send(Message::Text(message))
+                .await
+                .map_err(WebSocketError);
+        }
+
+        self.search = None;
+        self.game.do_move(result.best_move);
+        self.send_current_board().await.map_err(WebSocketError)?;
+        self.start_bot_search();
+        Ok(())
+    }
+
+    /// Starts a search on a blocking worker thread if a bot is to move.
+    /// The result arrives as a `BotMessage`.
+    fn start_bot_search(&mut self) {
+        if self.search.is_some() {
+            return;
+        }
+
+        let board = *self.current_board();
 
-            if !board.has_moves() {
-                return Ok(());
+        let Some(bot) = self.game.get_current_bot() else {
+            return;
+        };
+
+        if !board.has_moves() {
+            return;
+        }
+
+        let search_id = self.next_search_id;
+        self.next_search_id += 1;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let limits = SearchLimits {
+            time: Some(BOT_TIME_LIMIT),
+            stop: Some(stop.clone()),
+            ..Default::default()
+        };
+
+        self.search = Some(RunningSearch {
+            id: search_id,
+            stop: stop.clone(),
+        });
+
+        let sender = self.bot_sender.clone();
+
+        tokio::task::spawn_blocking(move || {
+            std::thread::sleep(BOT_MOVE_DELAY);
+            if stop.load(Ordering::Relaxed) {
+                return;
             }
 
-            let move_index = bot.get_move(&board.position);
-            self.game.do_move(move_index);
+            let mut observer = |event: SearchEvent| {
+                if let SearchEvent::DepthCompleted(result) = event {
+                    let _ = sender.send(BotMessage::Progress(search_id, result.clone()));
+                }
+            };
 
-            self.send_current_board().await.map_err(WebSocketError)?;
+            // A cancelled search may still be holding the bot, this waits for it to stop
+            let mut bot = bot.lock().unwrap();
+            let result = bot.search(&board.position, &limits, &mut observer);
+
+            // The session may have been closed in the meantime
+            let _ = sender.send(BotMessage::Done(search_id, result));
+        });
+    }
 
-            sleep(Duration::from_millis(100)).await;
+    /// Stops the running search, if any. Its result will be discarded.
+    fn cancel_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.stop.store(true, Ordering::Relaxed);
         }
     }
 
+    async fn handle_explain(&mut self, args: (&String, &Value)) -> Result<(), HandlerError> {
+        let explanation = explain(&self.current_board().position)
+            .map_err(|error| HandlerValueError((args.0.clone(), args.1.to_string()), error))?;
+
+        self.ws_sender
+            .send(Message::Text(explanation_message(&explanation)))
+            .await
+            .map_err(WebSocketError)
+    }
+
     fn current_board(&self) -> &Board {
         self.game.current_board()
     }
 }
 
-pub async fn handle_socket(socket: WebSocket) {
+/// Lists the bots players can choose, so the interface hides the unavailable ones
+fn bots_message() -> String {
+    json!({ "bots": available_bots() }).to_string()
+}
+
+fn thinking_message(result: &SearchResult) -> String {
+    let pv: Vec<String> = result
+        .principal_variation
+        .iter()
+        .map(|&move_| square_name(move_))
+        .collect();
+
+    json!({
+        "thinking": {
+            "depth": result.depth,
+            "is_endgame": result.is_endgame,
+            "is_book": result.is_book,
+            "selectivity": result.selectivity.percentage(),
+            "best_move": result.best_move,
+            "score": result.score,
+            "nodes": result.nodes,
+            "time": result.duration.as_secs_f64(),
+            "pv": pv,
+        }
+    })
+    .to_string()
+}
+
+/// Breaks the evaluation of the current position down by pattern, for the player to
+/// move, so the interface can highlight the patterns that drive it
+fn explanation_message(explanation: &Explanation) -> String {
+    let features: Vec<Value> = explanation
+        .features
+        .iter()
+        .map(|feature| {
+            json!({
+                "feature": feature.feature,
+                "squares": feature.squares,
+                "index": feature.index,
+                "weight": feature.weight,
+            })
+        })
+        .collect();
+
+    json!({
+        "explanation": {
+            "score": explanation.score,
+            "total": explanation.total,
+            "features": features,
+        }
+    })
+    .to_string()
+}
+
+pub async fn handle_socket(socket: WebSocket, bot_options: BotOptions) {
     // split socket to facilitate testing
     let (ws_sender, ws_receiver) = socket.split();
 
-    let mut session = GameSession::new(ws_sender, ws_receiver);
+    let mut session = GameSession::new(ws_sender, ws_receiver, bot_options);
 
     if let Err(err) = session.run().await {
         eprintln!("WS error: {}", err);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::app::{create_router, DEFAULT_ASSETS_DIR};
+    use std::net::{SocketAddr, TcpListener};
+    use std::path::Path;
+    use tokio::net::TcpStream;
+    use tokio::time::timeout;
+    use tokio_tungstenite::{connect_async, tungstenite, MaybeTlsStream, WebSocketStream};
+
+    type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    fn start_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = axum::Server::from_tcp(listener).unwrap().serve(
+            create_router(Path::new(DEFAULT_ASSETS_DIR), BotOptions::default()).into_make_service(),
+        );
+        tokio::spawn(server);
+
+        addr
+    }
+
+    async fn receive(ws: &mut Client) -> String {
+        match ws.next().await.unwrap().unwrap() {
+            tungstenite::Message::Text(text) => text,
+            other => panic!("Unexpected message: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_new_game_cancels_bot_search() {
+        let addr = start_server();
+        let (mut ws, _) = connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+
+        let initial_board = Board::new().as_ws_message();
+
+        assert_eq!(receive(&mut ws).await, initial_board);
+
+        for command in [
+            r#"{"set_white_player": "squared"}"#,
+            r#"{"human_move": 19}"#,
+            r#"{"new_game": null}"#,
+        ] {
+            ws.send(tungstenite::Message::Text(command.to_string()))
+                .await
+                .unwrap();
+        }
+
+        // The bot may or may not have moved before the new game started
+        loop {
+            let message = receive(&mut ws).await;
+            if message == initial_board {
+                break;
+            }
+        }
+
+        // Black is human, so nothing should arrive after the new game, not even a stale bot move
+        let next = timeout(Duration::from_millis(500), receive(&mut ws)).await;
+        assert!(next.is_err(), "Unexpected message: {:?}", next);
+    }
+
+    #[tokio::test]
+    async fn test_bots_message() {
+        let addr = start_server();
+        let (mut ws, _) = connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+
+        assert_eq!(receive(&mut ws).await, Board::new().as_ws_message());
+
+        let message: Value = serde_json::from_str(&receive(&mut ws).await).unwrap();
+        assert_eq!(message, json!({ "bots": available_bots() }));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_player() {
+        let addr = start_server();
+        let (mut ws, _) = connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+        receive(&mut ws).await;
+        receive(&mut ws).await;
+
+        for command in [
+            r#"{"set_black_player": 5}"#,
+            r#"{"set_white_player": null}"#,
+            r#"{"new_game": null}"#,
+        ] {
+            ws.send(tungstenite::Message::Text(command.to_string()))
+                .await
+                .unwrap();
+        }
+
+        // Invalid values are reported, the session keeps running
+        assert_eq!(receive(&mut ws).await, Board::new().as_ws_message());
+    }
+
+    #[tokio::test]
+    async fn test_explain() {
+        let addr = start_server();
+        let (mut ws, _) = connect_async(format!("ws://{}/ws", addr)).await.unwrap();
+        receive(&mut ws).await;
+        receive(&mut ws).await;
+
+        ws.send(tungstenite::Message::Text(
+            r#"{"explain": null}"#.to_string(),
+        ))
+        .await
+        .unwrap();
+
+        let message: Value = serde_json::from_str(&receive(&mut ws).await).unwrap();
+        let expected = explain(&Board::new().position).unwrap();
+        assert_eq!(message["explanation"]["score"], expected.score);
+        assert_eq!(message["explanation"]["total"], expected.total);
+
+        let features = message["explanation"]["features"].as_array().unwrap();
+        assert_eq!(features.len(), expected.features.len());
+        assert_eq!(features[0]["squares"], json!(expected.features[0].squares));
+        assert_eq!(features[0]["weight"], expected.features[0].weight);
+    }
+}
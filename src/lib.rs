@@ -0,0 +1,41 @@
+//! Othello engine using the search and evaluation of Edax.
+//!
+//! The items re-exported here are the stable API: parsing positions, generating and
+//! playing moves, and searching with a [`Bot`]. The modules give access to the engine
+//! internals as well, which change as the engine does.
+//!
+//! ```
+//! use swap::{get_bot, square_name, Position, SearchEvent, SearchLimits};
+//!
+//! let position: Position = "f5d6c3".parse().unwrap();
+//! assert_eq!(position.get_moves().count_ones(), 4);
+//!
+//! // The edax bot needs its evaluation weights, the squared bot is always available
+//! let mut bot = get_bot("squared").unwrap();
+//! let limits = SearchLimits {
+//!     depth: Some(4),
+//!     ..Default::default()
+//! };
+//! let result = bot.search(&position, &limits, &mut |_: SearchEvent| {});
+//!
+//! assert!(position.is_valid_move(result.best_move));
+//! println!("{} {}", square_name(result.best_move), result.score);
+//! ```
+//!
+//! The web interface is in `frontend`, behind the `server` feature, which is on by
+//! default. Depend on the crate with `default-features = false` to leave out the web
+//! server and its dependencies.
+
+pub mod bot;
+#[cfg(feature = "embedded-data")]
+mod embedded;
+#[cfg(feature = "server")]
+pub mod frontend;
+pub mod othello;
+
+pub use bot::{
+    get_bot, Bot, RootMoveScore, SearchEvent, SearchLimits, SearchObserver, SearchResult,
+    Selectivity,
+};
+pub use othello::position::{GameState, Position};
+pub use othello::squares::{parse_moves, parse_square, square_name, PASS};
@@ -1,10 +1,17 @@
-use frontend::app::run_app;
+use std::process::ExitCode;
 
-pub mod bot;
-pub mod frontend;
-pub mod othello;
+use cli::Args;
 
-#[tokio::main]
-async fn main() {
-    run_app().await;
+mod cli;
+
+fn main() -> ExitCode {
+    let args: Args = argh::from_env();
+
+    match cli::run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,113 @@
+use std::fmt::{self, Display};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Implementation of move generation and flipping behind `get_moves` and `get_flipped`.
+/// By default the fastest one the CPU supports is used, the others are for comparison.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Backend {
+    /// Kogge-Stone move generation and Edax's bitscan flipping, for any CPU
+    Scalar,
+
+    /// Flipping with the `pext` and `pdep` instructions of BMI2
+    Bmi2,
+
+    /// Move generation and flipping of four directions at once with AVX2
+    Avx2,
+}
+
+/// Backend in use, or `UNSELECTED` before the first call to `backend`
+static BACKEND: AtomicU8 = AtomicU8::new(UNSELECTED);
+
+const UNSELECTED: u8 = u8::MAX;
+
+impl Backend {
+    const ALL: [Backend; 3] = [Backend::Scalar, Backend::Bmi2, Backend::Avx2];
+
+    /// Returns true if the CPU has the instructions this backend needs
+    pub fn is_supported(self) -> bool {
+        match self {
+            Backend::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            Backend::Bmi2 => cfg!(target_feature = "bmi2") || is_x86_feature_detected!("bmi2"),
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => cfg!(target_feature = "avx2") || is_x86_feature_detected!("avx2"),
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => false,
+        }
+    }
+
+    /// Returns all backends the CPU supports, starting with `Scalar`
+    pub fn supported() -> Vec<Backend> {
+        Self::ALL.into_iter().filter(|b| b.is_supported()).collect()
+    }
+
+    /// Returns the fastest supported backend
+    pub fn best() -> Backend {
+        // BMI2 flipping benchmarks slower than AVX2 flipping, even though it needs fewer instructions
+        [Backend::Avx2, Backend::Bmi2]
+            .into_iter()
+            .find(|b| b.is_supported())
+            .unwrap_or(Backend::Scalar)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Scalar => "scalar",
+            Backend::Bmi2 => "bmi2",
+            Backend::Avx2 => "avx2",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Backend> {
+        Self::ALL.into_iter().find(|b| b.name() == name)
+    }
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Returns the backend in use, selecting the best one on the first call
+#[inline]
+pub fn backend() -> Backend {
+    match BACKEND.load(Ordering::Relaxed) {
+        UNSELECTED => {
+            let best = Backend::best();
+            BACKEND.store(best as u8, Ordering::Relaxed);
+            best
+        }
+        index => Backend::ALL[index as usize],
+    }
+}
+
+/// Switches all move generation and flipping to `backend`, for all threads
+pub fn set_backend(backend: Backend) -> Result<(), String> {
+    if !backend.is_supported() {
+        return Err(format!("This CPU does not support the {} backend", backend));
+    }
+
+    BACKEND.store(backend as u8, Ordering::Relaxed);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend() {
+        assert!(Backend::supported().contains(&Backend::Scalar));
+        assert!(Backend::best().is_supported());
+
+        for backend in Backend::ALL {
+            assert_eq!(Backend::from_name(backend.name()), Some(backend));
+            assert_eq!(set_backend(backend).is_ok(), backend.is_supported());
+        }
+        assert_eq!(Backend::from_name("sse"), None);
+
+        set_backend(Backend::best()).unwrap();
+        assert_eq!(backend(), Backend::best());
+    }
+}
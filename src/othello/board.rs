@@ -118,6 +118,13 @@ impl Board {
             }
         }
 
+        let black_stable = self.black_stable_discs();
+        let white_stable = self.white_stable_discs();
+        let stable_discs = black_stable | white_stable;
+        let stable: Vec<_> = (0..64)
+            .filter(|i| stable_discs & (1u64 << i) != 0)
+            .collect();
+
         let turn = if self.turn == BLACK { "black" } else { "white" };
 
         json!({
@@ -125,6 +132,9 @@ impl Board {
             "white": white,
             "turn": turn,
             "moves": moves,
+            "stable": stable,
+            "black_stable": black_stable.count_ones(),
+            "white_stable": white_stable.count_ones(),
         })
         .to_string()
     }
@@ -149,6 +159,22 @@ impl Board {
         }
     }
 
+    pub fn black_stable_discs(&self) -> u64 {
+        if self.turn == BLACK {
+            self.position.get_stable_discs()
+        } else {
+            self.position.get_opponent_stable_discs()
+        }
+    }
+
+    pub fn white_stable_discs(&self) -> u64 {
+        if self.turn == BLACK {
+            self.position.get_opponent_stable_discs()
+        } else {
+            self.position.get_stable_discs()
+        }
+    }
+
     pub fn do_move_cloned(&self, index: usize) -> Self {
         let position = self.position.do_move_cloned(index);
         Self::combine(position, opponent(self.turn))
@@ -245,6 +271,9 @@ mod tests {
         assert_eq!(json["white"], json!([27, 36]));
         assert_eq!(json["turn"], "black");
         assert_eq!(json["moves"], json!([19, 26, 37, 44]));
+        assert_eq!(json["stable"], json!([]));
+        assert_eq!(json["black_stable"], 0);
+        assert_eq!(json["white_stable"], 0);
     }
 
     #[test]
@@ -312,7 +341,7 @@ mod tests {
     fn test_pass() {
         let board = Board::new();
 
-        let mut passed = board.clone();
+        let mut passed = board;
         passed.pass();
 
         assert_eq!(passed.black_discs(), board.black_discs());
@@ -324,7 +353,7 @@ mod tests {
     fn test_pass_twice() {
         let board = Board::new();
 
-        let mut passed = board.clone();
+        let mut passed = board;
         passed.pass();
         passed.pass();
 
@@ -413,4 +442,16 @@ mod tests {
         assert_eq!(cloned.position.player, 0x0000001000000000);
         assert_eq!(cloned.position.opponent, 0x0000000818080000);
     }
+
+    #[test]
+    fn test_as_ws_message_stable() {
+        // Black owns A1 and B1, white owns H1, it's white's turn
+        let board = Board::new_from_bitboards(0x03, 0x80, WHITE);
+        let message = board.as_ws_message();
+        let json: Value = serde_json::from_str(&message).unwrap();
+
+        assert_eq!(json["stable"], json!([0, 1, 7]));
+        assert_eq!(json["black_stable"], 2);
+        assert_eq!(json["white_stable"], 1);
+    }
 }
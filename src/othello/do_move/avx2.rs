@@ -0,0 +1,109 @@
+// Flipping with AVX2: the four directions horizontal, diagonal, anti-diagonal and
+// vertical each get a 64-bit lane, which are filled from the move in both ways at once.
+
+use std::arch::x86_64::*;
+
+use crate::othello::position::Position;
+
+/// Returns the discs flipped when `player` plays `index`, without doing the move
+///
+/// # Safety
+///
+/// The CPU must support AVX2, see `Backend::is_supported`.
+#[target_feature(enable = "avx2")]
+pub fn get_flipped_avx2(player: u64, opponent: u64, index: usize) -> u64 {
+    let shift = _mm256_set_epi64x(8, 9, 7, 1);
+
+    // Opponent discs that can be flipped in each direction, edges wrap around otherwise
+    let inner = 0x7e7e7e7e7e7e7e7e_u64 as i64;
+    let pp = _mm256_set1_epi64x(player as i64);
+    let mo = _mm256_and_si256(
+        _mm256_set1_epi64x(opponent as i64),
+        _mm256_set_epi64x(-1, inner, inner, inner),
+    );
+    let move_ = _mm256_set1_epi64x(1i64 << index);
+
+    // Runs of opponent discs starting next to the move
+    let mut flip_l = _mm256_and_si256(mo, _mm256_sllv_epi64(move_, shift));
+    let mut flip_r = _mm256_and_si256(mo, _mm256_srlv_epi64(move_, shift));
+    for _ in 0..5 {
+        flip_l = _mm256_or_si256(
+            flip_l,
+            _mm256_and_si256(mo, _mm256_sllv_epi64(flip_l, shift)),
+        );
+        flip_r = _mm256_or_si256(
+            flip_r,
+            _mm256_and_si256(mo, _mm256_srlv_epi64(flip_r, shift)),
+        );
+    }
+
+    // Runs only flip if a player disc follows them
+    let zero = _mm256_setzero_si256();
+    let outflank_l = _mm256_and_si256(pp, _mm256_sllv_epi64(flip_l, shift));
+    let outflank_r = _mm256_and_si256(pp, _mm256_srlv_epi64(flip_r, shift));
+    flip_l = _mm256_andnot_si256(_mm256_cmpeq_epi64(outflank_l, zero), flip_l);
+    flip_r = _mm256_andnot_si256(_mm256_cmpeq_epi64(outflank_r, zero), flip_r);
+
+    or_lanes(_mm256_or_si256(flip_l, flip_r))
+}
+
+/// Returns the bitwise or of the four lanes
+///
+/// # Safety
+///
+/// The CPU must support AVX2, see `Backend::is_supported`.
+#[target_feature(enable = "avx2")]
+pub fn or_lanes(x: __m256i) -> u64 {
+    let x = _mm_or_si128(_mm256_castsi256_si128(x), _mm256_extracti128_si256(x, 1));
+    let x = _mm_or_si128(x, _mm_unpackhi_epi64(x, x));
+    _mm_cvtsi128_si64(x) as u64
+}
+
+/// # Safety
+///
+/// The CPU must support AVX2, see `Backend::is_supported`.
+#[target_feature(enable = "avx2")]
+pub fn do_move_avx2(position: &mut Position, index: usize) -> u64 {
+    let flips = get_flipped_avx2(position.player, position.opponent, index);
+
+    position.player |= flips | (1u64 << index);
+    position.opponent ^= flips;
+
+    std::mem::swap(&mut position.player, &mut position.opponent);
+
+    flips
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::othello::backend::Backend;
+    use crate::othello::do_move::simple::do_move_simple;
+    use crate::othello::get_moves::tests::move_test_cases;
+
+    #[test]
+    fn test_do_move_avx2() {
+        if !Backend::Avx2.is_supported() {
+            return;
+        }
+
+        for position in &move_test_cases() {
+            let mut remaining_moves = position.get_moves();
+
+            while remaining_moves != 0 {
+                let move_ = remaining_moves.trailing_zeros() as usize;
+                remaining_moves &= remaining_moves - 1;
+
+                let mut simple_after = *position;
+                let simple_flipped = do_move_simple(&mut simple_after, move_);
+
+                let mut avx2_after = *position;
+                // SAFETY: the CPU supports AVX2, as checked above
+                let avx2_flipped = unsafe { do_move_avx2(&mut avx2_after, move_) };
+
+                assert_eq!(avx2_flipped, simple_flipped, "{:?} {}", position, move_);
+                assert_eq!(avx2_after, simple_after);
+            }
+        }
+    }
+}
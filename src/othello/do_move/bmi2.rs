@@ -0,0 +1,197 @@
+// Flipping with BMI2: the four lines through the move are packed into bytes with `pext`,
+// flipped with lookup tables and spread back onto the board with `pdep`.
+
+use std::arch::x86_64::{_pdep_u64, _pext_u64};
+
+use crate::othello::position::Position;
+
+/// Masks of the row, column, diagonal and anti-diagonal through each square
+const LINE_MASKS: [[u64; 4]; 64] = {
+    let mut masks = [[0; 4]; 64];
+
+    let mut square: usize = 0;
+    while square < 64 {
+        let row = square as i32 / 8;
+        let col = square as i32 % 8;
+
+        let mut other: usize = 0;
+        while other < 64 {
+            let other_row = other as i32 / 8;
+            let other_col = other as i32 % 8;
+            let bit = 1u64 << other;
+
+            if other_row == row {
+                masks[square][0] |= bit;
+            }
+            if other_col == col {
+                masks[square][1] |= bit;
+            }
+            if other_row - other_col == row - col {
+                masks[square][2] |= bit;
+            }
+            if other_row + other_col == row + col {
+                masks[square][3] |= bit;
+            }
+            other += 1;
+        }
+        square += 1;
+    }
+    masks
+};
+
+/// Position of each square in the packed bytes of its lines
+const LINE_POSITIONS: [[usize; 4]; 64] = {
+    let mut positions = [[0; 4]; 64];
+
+    let mut square = 0;
+    while square < 64 {
+        let mut line = 0;
+        while line < 4 {
+            let below = LINE_MASKS[square][line] & ((1u64 << square) - 1);
+            positions[square][line] = below.count_ones() as usize;
+            line += 1;
+        }
+        square += 1;
+    }
+    positions
+};
+
+/// Squares of a packed line where a move at each position could outflank the opponent
+/// discs, indexed by position and opponent discs of the line
+static OUTFLANK: [[u8; 256]; 8] = {
+    let mut table = [[0; 256]; 8];
+
+    let mut position = 0;
+    while position < 8 {
+        let mut opponent = 0;
+        while opponent < 256 {
+            let mut outflank = 0;
+
+            let mut x = position + 1;
+            while x < 8 && opponent & (1 << x) != 0 {
+                x += 1;
+            }
+            if x > position + 1 && x < 8 {
+                outflank |= 1 << x;
+            }
+
+            let mut x = position as i32 - 1;
+            while x >= 0 && opponent & (1 << x) != 0 {
+                x -= 1;
+            }
+            if x < position as i32 - 1 && x >= 0 {
+                outflank |= 1 << x;
+            }
+
+            table[position][opponent] = outflank;
+            opponent += 1;
+        }
+        position += 1;
+    }
+    table
+};
+
+/// Discs of a packed line flipped by a move at each position, indexed by position
+/// and the squares where the player outflanks the opponent
+static FLIPPED: [[u8; 256]; 8] = {
+    let mut table = [[0; 256]; 8];
+
+    let mut position = 0;
+    while position < 8 {
+        let mut outflank = 0;
+        while outflank < 256 {
+            let mut flipped = 0u32;
+
+            let mut x = 0;
+            while x < 8 {
+                if outflank & (1 << x) != 0 {
+                    if x > position {
+                        flipped |= (1 << x) - (2 << position);
+                    } else if x < position {
+                        flipped |= (1 << position) - (2 << x);
+                    }
+                }
+                x += 1;
+            }
+
+            table[position][outflank] = flipped as u8;
+            outflank += 1;
+        }
+        position += 1;
+    }
+    table
+};
+
+/// Returns the discs flipped when `player` plays `index`, without doing the move
+///
+/// # Safety
+///
+/// The CPU must support BMI2, see `Backend::is_supported`.
+#[target_feature(enable = "bmi2")]
+pub fn get_flipped_bmi2(player: u64, opponent: u64, index: usize) -> u64 {
+    let masks = &LINE_MASKS[index];
+    let positions = &LINE_POSITIONS[index];
+    let mut flipped = 0;
+
+    for line in 0..4 {
+        let mask = masks[line];
+        let position = positions[line];
+
+        let line_player = _pext_u64(player, mask) as usize;
+        let line_opponent = _pext_u64(opponent, mask) as usize;
+
+        let outflank = OUTFLANK[position][line_opponent] as usize & line_player;
+        flipped |= _pdep_u64(FLIPPED[position][outflank] as u64, mask);
+    }
+
+    flipped
+}
+
+/// # Safety
+///
+/// The CPU must support BMI2, see `Backend::is_supported`.
+#[target_feature(enable = "bmi2")]
+pub fn do_move_bmi2(position: &mut Position, index: usize) -> u64 {
+    let flips = get_flipped_bmi2(position.player, position.opponent, index);
+
+    position.player |= flips | (1u64 << index);
+    position.opponent ^= flips;
+
+    std::mem::swap(&mut position.player, &mut position.opponent);
+
+    flips
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::othello::backend::Backend;
+    use crate::othello::do_move::simple::do_move_simple;
+    use crate::othello::get_moves::tests::move_test_cases;
+
+    #[test]
+    fn test_do_move_bmi2() {
+        if !Backend::Bmi2.is_supported() {
+            return;
+        }
+
+        for position in &move_test_cases() {
+            let mut remaining_moves = position.get_moves();
+
+            while remaining_moves != 0 {
+                let move_ = remaining_moves.trailing_zeros() as usize;
+                remaining_moves &= remaining_moves - 1;
+
+                let mut simple_after = *position;
+                let simple_flipped = do_move_simple(&mut simple_after, move_);
+
+                let mut bmi2_after = *position;
+                // SAFETY: the CPU supports BMI2, as checked above
+                let bmi2_flipped = unsafe { do_move_bmi2(&mut bmi2_after, move_) };
+
+                assert_eq!(bmi2_flipped, simple_flipped, "{:?} {}", position, move_);
+                assert_eq!(bmi2_after, simple_after);
+            }
+        }
+    }
+}
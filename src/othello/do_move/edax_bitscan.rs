@@ -1749,8 +1749,13 @@ static FLIP: [FlipFn; 64] = [
     flip_e8, flip_f8, flip_g8, flip_h8,
 ];
 
+/// Returns the discs flipped when `player` plays `index`, without doing the move
+pub fn get_flipped_edax_bitscan(player: u64, opponent: u64, index: usize) -> u64 {
+    FLIP[index](player, opponent)
+}
+
 pub fn do_move_edax_bitscan(position: &mut Position, index: usize) -> u64 {
-    let flips = FLIP[index](position.player, position.opponent);
+    let flips = get_flipped_edax_bitscan(position.player, position.opponent, index);
 
     position.player |= flips | (1u64 << index);
     position.opponent ^= flips;
@@ -1802,7 +1807,7 @@ mod tests {
                     println!("edax_bitscan:");
                     println!("{}", edax_bitscan_after);
 
-                    assert!(false);
+                    panic!();
                 }
             }
         }
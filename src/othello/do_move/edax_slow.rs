@@ -88,7 +88,7 @@ mod tests {
                     println!("edax_slow:");
                     println!("{}", edax_slow_after);
 
-                    assert!(false);
+                    panic!();
                 }
             }
         }
@@ -1,9 +1,33 @@
+#[cfg(target_arch = "x86_64")]
+pub mod avx2;
+#[cfg(target_arch = "x86_64")]
+pub mod bmi2;
 pub mod edax_bitscan;
 pub mod edax_slow;
 pub mod simple;
 
+use super::backend::{backend, Backend};
 use super::position::Position;
 
 pub fn do_move(position: &mut Position, index: usize) -> u64 {
-    edax_bitscan::do_move_edax_bitscan(position, index)
+    let flips = get_flipped(position.player, position.opponent, index);
+
+    position.player |= flips | (1u64 << index);
+    position.opponent ^= flips;
+
+    std::mem::swap(&mut position.player, &mut position.opponent);
+
+    flips
+}
+
+#[inline]
+pub fn get_flipped(player: u64, opponent: u64, index: usize) -> u64 {
+    match backend() {
+        // SAFETY: backends are only used if the CPU supports them
+        #[cfg(target_arch = "x86_64")]
+        Backend::Avx2 => unsafe { avx2::get_flipped_avx2(player, opponent, index) },
+        #[cfg(target_arch = "x86_64")]
+        Backend::Bmi2 => unsafe { bmi2::get_flipped_bmi2(player, opponent, index) },
+        _ => edax_bitscan::get_flipped_edax_bitscan(player, opponent, index),
+    }
 }
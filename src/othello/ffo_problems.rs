@@ -1,6 +1,4 @@
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
 
 use super::position::Position;
 
@@ -11,14 +9,21 @@ pub struct Problem {
     pub solutions: HashMap<usize, isize>,
 }
 
+#[cfg(not(feature = "embedded-data"))]
+fn read_ffo_file() -> String {
+    std::fs::read_to_string("assets/ffo_problems.txt").expect("Failed to read FFO problems file")
+}
+
+#[cfg(feature = "embedded-data")]
+fn read_ffo_file() -> String {
+    crate::embedded::ffo_problems()
+}
+
 pub fn parse_ffo_problems() -> Vec<Problem> {
-    let file = File::open("assets/ffo_problems.txt").expect("Failed to open FFO problems file");
-    let reader = BufReader::new(file);
+    let text = read_ffo_file();
     let mut problems = Vec::new();
 
-    for (line_idx, line) in reader.lines().enumerate() {
-        let line = line.expect("Failed to read line");
-
+    for (line_idx, line) in text.lines().enumerate() {
         // Split the line into position and solutions
         let parts: Vec<&str> = line.split(';').collect();
         if parts.len() < 2 {
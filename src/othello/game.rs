@@ -1,5 +1,10 @@
+use std::sync::{Arc, Mutex};
+
 use super::{board::Board, position::GameState};
-use crate::bot::{get_bot, Bot};
+use crate::bot::{new_bot_with, Bot, BotOptions};
+
+/// Bot that can be moved to another thread while it searches
+pub type SharedBot = Arc<Mutex<Box<dyn Bot>>>;
 
 pub struct Game {
     /// The boards in the game history
@@ -9,7 +14,10 @@ pub struct Game {
     offset: usize,
 
     /// The bots for each player
-    bots: [Option<Box<dyn Bot>>; 2],
+    bots: [Option<SharedBot>; 2],
+
+    /// Settings of the bots chosen with `set_player`
+    bot_options: BotOptions,
 }
 
 impl Default for Game {
@@ -20,27 +28,38 @@ impl Default for Game {
 
 impl Game {
     pub fn new() -> Self {
+        Self::with_bot_options(BotOptions::default())
+    }
+
+    /// Creates a game where the bots chosen with `set_player` use `bot_options`
+    pub fn with_bot_options(bot_options: BotOptions) -> Self {
         Self {
             boards: vec![Board::new()],
             offset: 0,
             bots: [None, None],
+            bot_options,
         }
     }
 
-    pub fn set_player(&mut self, color: usize, bot_name: &str) {
+    /// Lets the bot `bot_name` or a human with "human" play `color`. Fails if there is
+    /// no such bot or it is unavailable, then the player doesn't change.
+    pub fn set_player(&mut self, color: usize, bot_name: &str) -> Result<(), String> {
         let bot = if bot_name == "human" {
             None
         } else {
-            get_bot(bot_name)
+            Some(Arc::new(Mutex::new(new_bot_with(
+                bot_name,
+                &self.bot_options,
+            )?)))
         };
 
         self.bots[color] = bot;
+        Ok(())
     }
 
-    #[allow(clippy::borrowed_box)]
-    pub fn get_current_bot(&mut self) -> Option<&mut Box<dyn Bot>> {
+    pub fn get_current_bot(&self) -> Option<SharedBot> {
         let turn = self.current_board().turn;
-        self.bots[turn].as_mut()
+        self.bots[turn].clone()
     }
 
     fn has_human_turn(&self, board: &Board) -> bool {
@@ -127,19 +146,23 @@ mod tests {
         let mut game = Game::new();
 
         // Test human player
-        game.set_player(0, "human");
+        game.set_player(0, "human").unwrap();
         assert!(game.bots[0].is_none());
 
         // Test bot player
-        game.set_player(1, "random");
+        game.set_player(1, "random").unwrap();
+        assert!(game.bots[1].is_some());
+
+        // Unknown bots leave the player unchanged
+        assert!(game.set_player(1, "unknown").is_err());
         assert!(game.bots[1].is_some());
     }
 
     #[test]
     fn test_get_current_bot() {
         let mut game = Game::new();
-        game.set_player(0, "random");
-        game.set_player(1, "human");
+        game.set_player(0, "random").unwrap();
+        game.set_player(1, "human").unwrap();
 
         // First turn (bot)
         assert!(game.get_current_bot().is_some());
@@ -152,8 +175,8 @@ mod tests {
     #[test]
     fn test_has_human_turn() {
         let mut game = Game::new();
-        game.set_player(0, "human");
-        game.set_player(1, "random");
+        game.set_player(0, "human").unwrap();
+        game.set_player(1, "random").unwrap();
 
         assert!(game.has_human_turn(game.current_board()));
 
@@ -165,8 +188,8 @@ mod tests {
     #[test]
     fn test_undo_redo() {
         let mut game = Game::new();
-        game.set_player(0, "human");
-        game.set_player(1, "random");
+        game.set_player(0, "human").unwrap();
+        game.set_player(1, "random").unwrap();
 
         // Make some moves
         game.do_move(19); // Human move
@@ -232,7 +255,7 @@ mod tests {
     #[test]
     fn test_current_board() {
         let mut game = Game::new();
-        let initial_board = game.current_board().clone();
+        let initial_board = *game.current_board();
 
         game.do_move(19);
         assert_ne!(game.current_board(), &initial_board);
@@ -1,7 +1,20 @@
+use crate::othello::backend::{backend, Backend};
 use crate::othello::position::Position;
 
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+#[cfg(target_arch = "x86_64")]
+use crate::othello::do_move::avx2::or_lanes;
+
+#[inline]
 pub fn get_moves(player: u64, opponent: u64) -> u64 {
-    get_moves_fancy(player, opponent)
+    match backend() {
+        // SAFETY: the backend is only used if the CPU supports it
+        #[cfg(target_arch = "x86_64")]
+        Backend::Avx2 => unsafe { get_moves_avx2(player, opponent) },
+        _ => get_moves_fancy(player, opponent),
+    }
 }
 
 pub fn get_moves_simple(player: u64, opponent: u64) -> u64 {
@@ -54,6 +67,56 @@ pub fn get_moves_fancy(player: u64, opponent: u64) -> u64 {
     moves
 }
 
+/// Like `get_moves_fancy`, with the four directions in the lanes of a vector
+///
+/// # Safety
+///
+/// The CPU must support AVX2, see `Backend::is_supported`.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+pub fn get_moves_avx2(player: u64, opponent: u64) -> u64 {
+    let shift = _mm256_set_epi64x(8, 9, 7, 1);
+    let shift2 = _mm256_add_epi64(shift, shift);
+
+    let inner = 0x7e7e7e7e7e7e7e7e_u64 as i64;
+    let pp = _mm256_set1_epi64x(player as i64);
+    let mo = _mm256_and_si256(
+        _mm256_set1_epi64x(opponent as i64),
+        _mm256_set_epi64x(-1, inner, inner, inner),
+    );
+
+    let mut flip_l = _mm256_and_si256(mo, _mm256_sllv_epi64(pp, shift));
+    let mut flip_r = _mm256_and_si256(mo, _mm256_srlv_epi64(pp, shift));
+    flip_l = _mm256_or_si256(
+        flip_l,
+        _mm256_and_si256(mo, _mm256_sllv_epi64(flip_l, shift)),
+    );
+    flip_r = _mm256_or_si256(
+        flip_r,
+        _mm256_and_si256(mo, _mm256_srlv_epi64(flip_r, shift)),
+    );
+
+    let mask_l = _mm256_and_si256(mo, _mm256_sllv_epi64(mo, shift));
+    let mask_r = _mm256_and_si256(mo, _mm256_srlv_epi64(mo, shift));
+    for _ in 0..2 {
+        flip_l = _mm256_or_si256(
+            flip_l,
+            _mm256_and_si256(mask_l, _mm256_sllv_epi64(flip_l, shift2)),
+        );
+        flip_r = _mm256_or_si256(
+            flip_r,
+            _mm256_and_si256(mask_r, _mm256_srlv_epi64(flip_r, shift2)),
+        );
+    }
+
+    let moves = _mm256_or_si256(
+        _mm256_sllv_epi64(flip_l, shift),
+        _mm256_srlv_epi64(flip_r, shift),
+    );
+
+    or_lanes(moves) & !(player | opponent)
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -82,7 +145,7 @@ pub mod tests {
                     let move_x = x + (distance + 1) * dx;
                     let move_y = y + (distance + 1) * dy;
 
-                    if move_x < 0 || move_x >= 8 || move_y < 0 || move_y >= 8 {
+                    if !(0..8).contains(&move_x) || !(0..8).contains(&move_y) {
                         continue;
                     }
 
@@ -125,4 +188,19 @@ pub mod tests {
             assert_eq!(simple_moves, fancy_moves);
         }
     }
+
+    #[test]
+    fn test_get_moves_avx2() {
+        if !Backend::Avx2.is_supported() {
+            return;
+        }
+
+        for test_case in move_test_cases() {
+            let simple_moves = get_moves_simple(test_case.player, test_case.opponent);
+            // SAFETY: the CPU supports AVX2, as checked above
+            let avx2_moves = unsafe { get_moves_avx2(test_case.player, test_case.opponent) };
+
+            assert_eq!(avx2_moves, simple_moves, "{:?}", test_case);
+        }
+    }
 }
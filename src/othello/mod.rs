@@ -1,7 +1,12 @@
+pub mod backend;
 pub mod board;
 pub mod do_move;
 pub mod ffo_problems;
 pub mod game;
 pub mod get_moves;
+pub mod perft;
 pub mod position;
 pub mod squares;
+pub mod stability;
+pub mod symmetry;
+pub mod zobrist;
@@ -0,0 +1,201 @@
+// Perft counts the positions reached after a number of moves. As in the published
+// perft tables for Othello, passes count as a move and a game that ends before the
+// requested depth counts as one position.
+
+use super::backend::{backend, Backend};
+use super::do_move::{self, edax_bitscan, edax_slow, simple};
+use super::get_moves::{self, get_moves_fancy, get_moves_simple};
+use super::position::Position;
+
+/// Perft of the start position for depths 0 to 14
+pub const START_PERFT: [u64; 15] = [
+    1,
+    4,
+    12,
+    56,
+    244,
+    1396,
+    8200,
+    55092,
+    390216,
+    3005288,
+    24571284,
+    212258800,
+    1939886636,
+    18429641748,
+    184042084512,
+];
+
+pub type GetMovesFn = fn(u64, u64) -> u64;
+pub type DoMoveFn = fn(&mut Position, usize) -> u64;
+
+/// A pair of move generation and move execution functions to run perft with
+#[derive(Clone)]
+pub struct MoveGenerator {
+    pub name: String,
+    pub get_moves: GetMovesFn,
+    pub do_move: DoMoveFn,
+}
+
+/// All combinations of the implementations of `get_moves` and `do_move` that the CPU
+/// supports, starting with the scalar ones of `Backend::Scalar`
+pub fn move_generators() -> Vec<MoveGenerator> {
+    let mut get_moves: Vec<(&str, GetMovesFn)> =
+        vec![("fancy", get_moves_fancy), ("simple", get_moves_simple)];
+
+    let mut do_move: Vec<(&str, DoMoveFn)> = vec![
+        ("edax_bitscan", edax_bitscan::do_move_edax_bitscan),
+        ("edax_slow", edax_slow::do_move_edax_slow),
+        ("simple", simple::do_move_simple),
+    ];
+
+    #[cfg(target_arch = "x86_64")]
+    {
+        if Backend::Avx2.is_supported() {
+            get_moves.push(("avx2", simd::get_moves_avx2));
+            do_move.push(("avx2", simd::do_move_avx2));
+        }
+        if Backend::Bmi2.is_supported() {
+            do_move.push(("bmi2", simd::do_move_bmi2));
+        }
+    }
+
+    let mut generators = vec![];
+    for &(get_moves_name, get_moves) in &get_moves {
+        for &(do_move_name, do_move) in &do_move {
+            generators.push(MoveGenerator {
+                name: format!("{}/{}", get_moves_name, do_move_name),
+                get_moves,
+                do_move,
+            });
+        }
+    }
+    generators
+}
+
+/// Safe wrappers of the SIMD implementations. They must only be called on CPUs
+/// with the instructions, which `move_generators` checks before listing them.
+#[cfg(target_arch = "x86_64")]
+mod simd {
+    use crate::othello::do_move::{avx2, bmi2};
+    use crate::othello::get_moves;
+    use crate::othello::position::Position;
+
+    pub fn get_moves_avx2(player: u64, opponent: u64) -> u64 {
+        // SAFETY: `move_generators` only lists this wrapper after
+        // `Backend::Avx2.is_supported()` confirmed that the CPU has AVX2
+        unsafe { get_moves::get_moves_avx2(player, opponent) }
+    }
+
+    pub fn do_move_avx2(position: &mut Position, index: usize) -> u64 {
+        // SAFETY: `move_generators` only lists this wrapper after
+        // `Backend::Avx2.is_supported()` confirmed that the CPU has AVX2
+        unsafe { avx2::do_move_avx2(position, index) }
+    }
+
+    pub fn do_move_bmi2(position: &mut Position, index: usize) -> u64 {
+        // SAFETY: `move_generators` only lists this wrapper after
+        // `Backend::Bmi2.is_supported()` confirmed that the CPU has BMI2
+        unsafe { bmi2::do_move_bmi2(position, index) }
+    }
+}
+
+/// Returns the move generation of `Position`, named after the backend in use
+pub fn position_generator() -> MoveGenerator {
+    MoveGenerator {
+        name: backend().to_string(),
+        get_moves: get_moves::get_moves,
+        do_move: do_move::do_move,
+    }
+}
+
+/// Returns the number of positions reached from `position` after `depth` moves
+pub fn perft(position: &Position, depth: u32) -> u64 {
+    perft_with(position, depth, &position_generator())
+}
+
+/// Like `perft`, with other move generation functions
+pub fn perft_with(position: &Position, depth: u32, generator: &MoveGenerator) -> u64 {
+    let mut position = *position;
+    count(&mut position, depth, generator)
+}
+
+fn count(position: &mut Position, depth: u32, generator: &MoveGenerator) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let mut moves = (generator.get_moves)(position.player, position.opponent);
+
+    if moves == 0 {
+        if (generator.get_moves)(position.opponent, position.player) == 0 {
+            // The game ended before reaching the depth
+            return 1;
+        }
+
+        position.pass();
+        let nodes = count(position, depth - 1, generator);
+        position.pass();
+        return nodes;
+    }
+
+    if depth == 1 {
+        return moves.count_ones() as u64;
+    }
+
+    let mut nodes = 0;
+    while moves != 0 {
+        let index = moves.trailing_zeros() as usize;
+        moves &= moves - 1;
+
+        let flips = (generator.do_move)(position, index);
+        nodes += count(position, depth - 1, generator);
+        position.undo_move(index, flips);
+    }
+
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::othello::position::XOT_POSITIONS;
+    use crate::othello::squares::*;
+
+    #[test]
+    fn test_start_perft() {
+        for (depth, expected) in START_PERFT.iter().enumerate().take(10) {
+            assert_eq!(perft(&Position::new(), depth as u32), *expected);
+        }
+    }
+
+    #[test]
+    fn test_move_generators_agree() {
+        let generators = move_generators();
+        assert!(generators.len() >= 6);
+        assert_eq!(generators[0].name, "fancy/edax_bitscan");
+
+        for position in XOT_POSITIONS.iter().take(20) {
+            let expected = perft(position, 4);
+
+            for generator in &generators {
+                assert_eq!(
+                    perft_with(position, 4, generator),
+                    expected,
+                    "{}",
+                    generator.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_perft_game_over() {
+        // Black takes all discs with A1, so the game ends
+        let position = Position::new_from_bitboards(1u64 << C1, 1u64 << B1);
+
+        for depth in 1..5 {
+            assert_eq!(perft(&position, depth), 1);
+        }
+    }
+}
@@ -2,22 +2,20 @@ use lazy_static::lazy_static;
 use rand::{rngs::ThreadRng, RngCore};
 use serde_json::Value;
 use std::fmt::{self, Display};
-use std::fs::File;
-use std::io::Read;
-use std::path::Path;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
 
 use super::board::BLACK;
 use super::do_move::do_move;
 use super::get_moves;
+use super::squares::{parse_moves, square_name};
+use super::stability;
+use super::symmetry::Symmetry;
+use super::zobrist;
 
 lazy_static! {
     pub static ref XOT_POSITIONS: Vec<Position> = {
-        let path = Path::new("assets/xot.json");
-        let mut file = File::open(path).expect("Failed to open XOT file");
-        let mut json_str = String::new();
-        file.read_to_string(&mut json_str)
-            .expect("Failed to read XOT file");
-
+        let json_str = read_xot_file();
         let json: Value = serde_json::from_str(&json_str).expect("Failed to parse JSON");
 
         fn parse_position(v: &Value) -> Position {
@@ -37,6 +35,16 @@ lazy_static! {
     };
 }
 
+#[cfg(not(feature = "embedded-data"))]
+fn read_xot_file() -> String {
+    std::fs::read_to_string("assets/xot.json").expect("Failed to read XOT file")
+}
+
+#[cfg(feature = "embedded-data")]
+fn read_xot_file() -> String {
+    crate::embedded::xot()
+}
+
 pub fn print_bitset(bitset: u64) {
     let mut output = String::new();
     output.push_str("+-A-B-C-D-E-F-G-H-+\n");
@@ -57,6 +65,7 @@ pub fn print_bitset(bitset: u64) {
     println!("{}", output);
 }
 
+/// Whether the player to move can play, has to pass or the game is over
 #[derive(PartialEq, Debug)]
 pub enum GameState {
     HasMoves,
@@ -64,6 +73,8 @@ pub enum GameState {
     Finished,
 }
 
+/// Position from the point of view of the player to move, as bitboards of the
+/// discs of both players with bit 0 for a1, bit 7 for h1 and bit 63 for h8
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
 pub struct Position {
     pub player: u64,
@@ -76,13 +87,64 @@ impl Default for Position {
     }
 }
 
+/// Hashes with `Position::hash`, so hash maps and hash tables agree on it
+impl Hash for Position {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(Position::hash(self));
+    }
+}
+
 impl Display for Position {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.ascii_art(BLACK))
     }
 }
 
+/// Parses a board as in Edax and the FFO problems: 64 squares from a1 to h8 with `X` or
+/// `*` for black, `O` for white and `-` or `.` for empty, then `X` or `O` for the color to
+/// move. Anything else is read as a transcript of moves from the start position, such as
+/// "f5d6c3", where passes are left out.
+impl FromStr for Position {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+
+        if text.len() != 65 {
+            let transcript: String = text.into_iter().collect();
+            let moves = parse_moves(&transcript)
+                .ok_or_else(|| "Not a board or a transcript of moves".to_string())?;
+            return Self::from_moves(&moves);
+        }
+
+        let mut black = 0;
+        let mut white = 0;
+
+        for (index, square) in text[..64].iter().enumerate() {
+            match square {
+                'X' | 'x' | '*' => black |= 1 << index,
+                'O' | 'o' => white |= 1 << index,
+                '-' | '.' => {}
+                _ => {
+                    return Err(format!(
+                        "Invalid square {:?} at {}",
+                        square,
+                        square_name(index)
+                    ))
+                }
+            }
+        }
+
+        match text[64] {
+            'X' | 'x' | '*' => Ok(Self::new_from_bitboards(black, white)),
+            'O' | 'o' => Ok(Self::new_from_bitboards(white, black)),
+            color => Err(format!("Invalid color to move {:?}", color)),
+        }
+    }
+}
+
 impl Position {
+    /// Returns the start position, with black to move
     pub fn new() -> Self {
         Self {
             player: 0x00000000810000000,
@@ -90,6 +152,7 @@ impl Position {
         }
     }
 
+    /// Returns a random opening of the XOT list
     pub fn new_xot() -> Self {
         let n = ThreadRng::default().next_u64() as usize;
         XOT_POSITIONS[n % XOT_POSITIONS.len()]
@@ -99,6 +162,45 @@ impl Position {
         Self { player, opponent }
     }
 
+    /// Plays `moves` from the start position, passing when there is no valid move
+    pub fn from_moves(moves: &[usize]) -> Result<Self, String> {
+        let mut position = Self::new();
+
+        for (ply, &move_) in moves.iter().enumerate() {
+            if !position.has_moves() {
+                position.pass();
+            }
+
+            if !position.is_valid_move(move_) {
+                return Err(format!(
+                    "Invalid move {} at ply {}",
+                    square_name(move_),
+                    ply + 1
+                ));
+            }
+            position.do_move(move_);
+        }
+
+        Ok(position)
+    }
+
+    /// Returns the board in the format read by `from_str`, with the player to move as `X`
+    pub fn to_board_string(&self) -> String {
+        let mut board: String = (0..64)
+            .map(|index| {
+                if self.player & (1 << index) != 0 {
+                    'X'
+                } else if self.opponent & (1 << index) != 0 {
+                    'O'
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        board.push_str(" X");
+        board
+    }
+
     pub fn shift(bitboard: u64, dir: i32) -> u64 {
         match dir {
             -9 => (bitboard & 0xfefefefefefefefe) << 7,
@@ -113,6 +215,7 @@ impl Position {
         }
     }
 
+    /// Returns the squares where the player to move can play
     pub fn get_moves(&self) -> u64 {
         get_moves::get_moves(self.player, self.opponent)
     }
@@ -125,6 +228,7 @@ impl Position {
         self.get_moves() != 0
     }
 
+    /// Returns true if the player to move can play `index`
     pub fn is_valid_move(&self, index: usize) -> bool {
         if index >= 64 {
             return false;
@@ -133,6 +237,7 @@ impl Position {
         self.get_moves() & (1u64 << index) != 0
     }
 
+    /// Gives the turn to the opponent without playing
     pub fn pass(&mut self) {
         std::mem::swap(&mut self.player, &mut self.opponent);
     }
@@ -152,16 +257,20 @@ impl Position {
         GameState::Finished
     }
 
+    /// Plays the valid move `index` and gives the turn to the opponent.
+    /// Returns the flipped discs, which `undo_move` needs.
     pub fn do_move(&mut self, index: usize) -> u64 {
         do_move(self, index)
     }
 
+    /// Takes back the move `index` that flipped `flips`
     pub fn undo_move(&mut self, index: usize, flips: u64) {
         std::mem::swap(&mut self.player, &mut self.opponent);
         self.player &= !(flips | (1u64 << index));
         self.opponent |= flips;
     }
 
+    /// Returns the position after the valid move `index`
     pub fn do_move_cloned(&self, index: usize) -> Self {
         let mut child = *self;
         child.do_move(index);
@@ -225,6 +334,8 @@ impl Position {
             .collect()
     }
 
+    /// Returns the score of a finished game for the player to move, with the empty
+    /// squares going to the winner
     pub fn final_score(&self) -> isize {
         let player = self.player.count_ones() as isize;
         let opponent = self.opponent.count_ones() as isize;
@@ -248,11 +359,54 @@ impl Position {
         let color = 2 - 2 * ((self.player >> index) & 1) - ((self.opponent >> index) & 1);
         color as usize
     }
+
+    /// Returns the discs of the player to move that can never be flipped.
+    /// Not all such discs are found, but all returned discs are stable.
+    pub fn get_stable_discs(&self) -> u64 {
+        stability::get_stable_discs(self.player, self.opponent)
+    }
+
+    /// Returns the discs of the opponent that can never be flipped, like `get_stable_discs`
+    pub fn get_opponent_stable_discs(&self) -> u64 {
+        stability::get_stable_discs(self.opponent, self.player)
+    }
+
+    pub fn count_stable(&self) -> u32 {
+        self.get_stable_discs().count_ones()
+    }
+
+    pub fn count_opponent_stable(&self) -> u32 {
+        self.get_opponent_stable_discs().count_ones()
+    }
+
+    /// Returns the Zobrist hash, computed from the bitboards. The searches and
+    /// `HashTable` call this on every probe, see `ZobristHash` for following a
+    /// position through moves instead.
+    pub fn hash(&self) -> u64 {
+        zobrist::hash(self)
+    }
+
+    pub fn transform(&self, symmetry: Symmetry) -> Self {
+        Self {
+            player: symmetry.apply(self.player),
+            opponent: symmetry.apply(self.opponent),
+        }
+    }
+
+    /// Returns the symmetric position with the smallest bitboards and the symmetry that
+    /// leads to it. Moves of the canonical position map back with `symmetry.inverse()`.
+    pub fn canonical(&self) -> (Self, Symmetry) {
+        Symmetry::all()
+            .map(|symmetry| (self.transform(symmetry), symmetry))
+            .min_by_key(|(position, _)| (position.player, position.opponent))
+            .unwrap()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::othello::board::WHITE;
+    use crate::othello::squares::*;
 
     use super::*;
 
@@ -516,7 +670,7 @@ mod tests {
     #[test]
     fn test_undo_move() {
         let mut position = Position::new();
-        let original = position.clone();
+        let original = position;
 
         // Do a move and store the flips
         let flips = position.do_move(19); // D3
@@ -560,4 +714,113 @@ mod tests {
 
         assert_eq!(found, expected);
     }
+
+    #[test]
+    fn test_transform() {
+        let position = Position::new().do_move_cloned(D3).do_move_cloned(C5);
+
+        for symmetry in Symmetry::all() {
+            let transformed = position.transform(symmetry);
+            assert_eq!(
+                transformed.get_moves(),
+                symmetry.apply(position.get_moves())
+            );
+            assert_eq!(transformed.transform(symmetry.inverse()), position);
+        }
+    }
+
+    #[test]
+    fn test_canonical() {
+        let position = Position::new();
+        let children: Vec<_> = position
+            .children()
+            .iter()
+            .map(|c| c.canonical().0)
+            .collect();
+
+        // All first moves are symmetric
+        assert!(children.iter().all(|child| *child == children[0]));
+        assert_eq!(children[0].canonical(), (children[0], Symmetry::IDENTITY));
+
+        for child in position.children() {
+            let (canonical, symmetry) = child.canonical();
+            assert_eq!(child.transform(symmetry), canonical);
+
+            // Moves of the canonical position map back to moves of the child
+            let moves = canonical.get_moves();
+            assert_eq!(symmetry.inverse().apply(moves), child.get_moves());
+        }
+
+        // XOT openings are all different, even under symmetry
+        let mut xot: Vec<_> = XOT_POSITIONS
+            .iter()
+            .map(|position| position.canonical().0)
+            .map(|position| (position.player, position.opponent))
+            .collect();
+        xot.sort();
+        xot.dedup();
+        assert_eq!(xot.len(), XOT_POSITIONS.len());
+    }
+
+    #[test]
+    fn test_stable_discs() {
+        assert_eq!(Position::new().count_stable(), 0);
+
+        // Black owns A1 to C1, white owns the rest of the first row
+        let position = Position::new_from_bitboards(0x07, 0xf8);
+        assert_eq!(position.get_stable_discs(), 0x07);
+        assert_eq!(position.get_opponent_stable_discs(), 0xf8);
+        assert_eq!(position.count_stable(), 3);
+        assert_eq!(position.count_opponent_stable(), 5);
+
+        let passed = Position::new_from_bitboards(0xf8, 0x07);
+        assert_eq!(passed.count_stable(), 5);
+        assert_eq!(passed.count_opponent_stable(), 3);
+    }
+
+    #[test]
+    fn test_from_str() {
+        let start = "---------------------------OX------XO--------------------------- X";
+        assert_eq!(start.parse(), Ok(Position::new()));
+        assert_eq!("".parse(), Ok(Position::new()));
+
+        let mut after_f5 = Position::new();
+        after_f5.do_move(F5);
+        assert_eq!("f5".parse(), Ok(after_f5));
+
+        let white_to_move = start.replace("X------XO", "X------XX").replace(" X", "o");
+        assert_eq!(
+            white_to_move.parse(),
+            Ok(Position::new_from_bitboards(
+                1 << D4,
+                (1 << E4) | (1 << D5) | (1 << E5)
+            ))
+        );
+
+        // The FFO problems are in the same format
+        let ffo = "--XXXXX--OOOXX-O-OOOXXOX-OXOXOXXOXXXOXXX--XOXOXX-XXXOOO--OOOOO-- X";
+        let position: Position = ffo.parse().unwrap();
+        assert_eq!(position.count_empty(), 14);
+        assert!(position.is_valid_move(G8));
+        assert_eq!(position.to_board_string(), ffo);
+        assert_eq!(Position::new().to_board_string(), start);
+
+        assert!(start.replace(" X", " Y").parse::<Position>().is_err());
+        assert!(start.replace("OX", "OZ").parse::<Position>().is_err());
+        assert!("f5f5".parse::<Position>().is_err());
+        assert!("f5d".parse::<Position>().is_err());
+    }
+
+    #[test]
+    fn test_from_moves() {
+        let mut position = Position::new();
+        position.do_move(F5);
+        position.do_move(D6);
+        assert_eq!(Position::from_moves(&[F5, D6]), Ok(position));
+
+        assert_eq!(
+            Position::from_moves(&[F5, A1]),
+            Err("Invalid move a1 at ply 2".to_string())
+        );
+    }
 }
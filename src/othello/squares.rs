@@ -62,3 +62,78 @@ pub const E8: usize = 60;
 pub const F8: usize = 61;
 pub const G8: usize = 62;
 pub const H8: usize = 63;
+
+/// Pseudo-move for passing a turn
+pub const PASS: usize = 64;
+
+/// Returns the name of a square, such as "d3", or "pass" for `PASS`
+pub fn square_name(index: usize) -> String {
+    if index == PASS {
+        return "pass".to_string();
+    }
+
+    let col = (b'a' + (index % 8) as u8) as char;
+    let row = index / 8 + 1;
+    format!("{}{}", col, row)
+}
+
+/// Returns the index of a square name such as "d3", case insensitive
+pub fn parse_square(name: &str) -> Option<usize> {
+    let [col, row] = name.as_bytes() else {
+        return None;
+    };
+
+    let col = col.to_ascii_lowercase().checked_sub(b'a')? as usize;
+    let row = row.checked_sub(b'1')? as usize;
+
+    if col >= 8 || row >= 8 {
+        return None;
+    }
+
+    Some(row * 8 + col)
+}
+
+/// Returns the squares of a transcript of moves such as "f5d6c3"
+pub fn parse_moves(transcript: &str) -> Option<Vec<usize>> {
+    if !transcript.len().is_multiple_of(2) || !transcript.is_ascii() {
+        return None;
+    }
+
+    (0..transcript.len())
+        .step_by(2)
+        .map(|i| parse_square(&transcript[i..i + 2]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_square_name() {
+        assert_eq!(square_name(A1), "a1");
+        assert_eq!(square_name(D3), "d3");
+        assert_eq!(square_name(H8), "h8");
+        assert_eq!(square_name(PASS), "pass");
+    }
+
+    #[test]
+    fn test_parse_square() {
+        for index in 0..64 {
+            assert_eq!(parse_square(&square_name(index)), Some(index));
+        }
+        assert_eq!(parse_square("D3"), Some(D3));
+        assert_eq!(parse_square("i1"), None);
+        assert_eq!(parse_square("a9"), None);
+        assert_eq!(parse_square("a"), None);
+        assert_eq!(parse_square("pass"), None);
+    }
+
+    #[test]
+    fn test_parse_moves() {
+        assert_eq!(parse_moves("f5d6C3"), Some(vec![F5, D6, C3]));
+        assert_eq!(parse_moves(""), Some(vec![]));
+        assert_eq!(parse_moves("f5d"), None);
+        assert_eq!(parse_moves("f5z9"), None);
+    }
+}
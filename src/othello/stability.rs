@@ -0,0 +1,255 @@
+// This is adapted from https://github.com/abulmo/edax-reversi/blob/master/src/board.c
+
+use lazy_static::lazy_static;
+
+/// Squares that are not on an edge
+const INNER_SQUARES: u64 = 0x007e7e7e7e7e7e00;
+
+lazy_static! {
+    /// Stable discs of the player on an edge, indexed by player and opponent edge bits
+    static ref EDGE_STABILITY: Vec<u8> = {
+        let mut table = vec![0; 256 * 256];
+        for player in 0..256 {
+            for opponent in 0..256 {
+                if player & opponent == 0 {
+                    table[player * 256 + opponent] =
+                        find_edge_stable(player as u32, opponent as u32, player as u32) as u8;
+                }
+            }
+        }
+        table
+    };
+
+    /// Masks of all horizontal, vertical, diagonal and anti-diagonal lines
+    static ref LINES: [Vec<u64>; 4] = {
+        let mut lines: [Vec<u64>; 4] = Default::default();
+
+        for i in 0..8 {
+            lines[0].push(0xff << (8 * i));
+            lines[1].push(0x0101010101010101 << i);
+        }
+
+        for sum in 0..15i32 {
+            let mut diagonal = 0;
+            let mut anti_diagonal = 0;
+            for row in 0..8i32 {
+                let col = sum - row;
+                if (0..8).contains(&col) {
+                    anti_diagonal |= 1u64 << (8 * row + col);
+                }
+                let col = sum - 7 + row;
+                if (0..8).contains(&col) {
+                    diagonal |= 1u64 << (8 * row + col);
+                }
+            }
+            lines[2].push(anti_diagonal);
+            lines[3].push(diagonal);
+        }
+
+        lines
+    };
+}
+
+/// Finds the stable discs of an edge by trying all sequences of moves on it.
+/// Both sides are assumed to be able to play on any empty square.
+fn find_edge_stable(player: u32, opponent: u32, mut stable: u32) -> u32 {
+    let empties = !(player | opponent) & 0xff;
+
+    stable &= player;
+    if stable == 0 || empties == 0 {
+        return stable;
+    }
+
+    for x in 0..8 {
+        if empties & (1 << x) == 0 {
+            continue;
+        }
+
+        let (new_player, new_opponent) = play_edge(player, opponent, x);
+        stable = find_edge_stable(new_player, new_opponent, stable);
+        if stable == 0 {
+            return stable;
+        }
+
+        let (new_opponent, new_player) = play_edge(opponent, player, x);
+        stable = find_edge_stable(new_player, new_opponent, stable);
+        if stable == 0 {
+            return stable;
+        }
+    }
+
+    stable
+}
+
+/// Plays `x` for `player` on an edge, returns the new player and opponent edges
+fn play_edge(mut player: u32, mut opponent: u32, x: u32) -> (u32, u32) {
+    player |= 1 << x;
+
+    if x > 1 {
+        let mut y = x - 1;
+        while y > 0 && opponent & (1 << y) != 0 {
+            y -= 1;
+        }
+        if player & (1 << y) != 0 {
+            let flipped = ((1 << x) - 1) & !((2 << y) - 1);
+            player ^= flipped;
+            opponent ^= flipped;
+        }
+    }
+
+    if x < 6 {
+        let mut y = x + 1;
+        while y < 8 && opponent & (1 << y) != 0 {
+            y += 1;
+        }
+        if player & (1 << y) != 0 {
+            let flipped = ((1 << y) - 1) & !((2 << x) - 1);
+            player ^= flipped;
+            opponent ^= flipped;
+        }
+    }
+
+    (player, opponent)
+}
+
+/// Packs the A column into 8 bits, with A1 as lowest bit
+fn pack_column(bitboard: u64) -> usize {
+    ((bitboard & 0x0101010101010101).wrapping_mul(0x0102040810204080) >> 56) as usize
+}
+
+/// Unpacks 8 bits into the A column, without the corners
+fn unpack_column(bits: u8) -> u64 {
+    (1..7)
+        .filter(|row| bits & (1 << row) != 0)
+        .fold(0, |column, row| column | (1 << (8 * row)))
+}
+
+fn edge_stability(player: usize, opponent: usize) -> u8 {
+    EDGE_STABILITY[player * 256 + opponent]
+}
+
+/// Returns the discs of `player` on the edges that can never be flipped.
+pub fn get_stable_edges(player: u64, opponent: u64) -> u64 {
+    edge_stability((player & 0xff) as usize, (opponent & 0xff) as usize) as u64
+        | (edge_stability((player >> 56) as usize, (opponent >> 56) as usize) as u64) << 56
+        | unpack_column(edge_stability(pack_column(player), pack_column(opponent)))
+        | unpack_column(edge_stability(
+            pack_column(player >> 7),
+            pack_column(opponent >> 7),
+        )) << 7
+}
+
+/// Returns the discs of `player` that can never be flipped.
+/// Not all such discs are found, but all returned discs are stable.
+pub fn get_stable_discs(player: u64, opponent: u64) -> u64 {
+    let mut stable = get_stable_edges(player, opponent);
+
+    let discs = player | opponent;
+    let full = LINES.each_ref().map(|lines| {
+        lines
+            .iter()
+            .filter(|&&line| discs & line == line)
+            .fold(0, |full, line| full | line)
+    });
+
+    // Discs on four full lines can't be flipped
+    stable |= player & full[0] & full[1] & full[2] & full[3];
+
+    // Discs are stable if every line is full or has a stable neighbour
+    loop {
+        let horizontal = (stable >> 1) | (stable << 1) | full[0];
+        let vertical = (stable >> 8) | (stable << 8) | full[1];
+        let anti_diagonal = (stable >> 7) | (stable << 7) | full[2];
+        let diagonal = (stable >> 9) | (stable << 9) | full[3];
+
+        let new_stable =
+            stable | (horizontal & vertical & anti_diagonal & diagonal & player & INNER_SQUARES);
+
+        if new_stable == stable {
+            return stable;
+        }
+        stable = new_stable;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::othello::ffo_problems::parse_ffo_problems;
+    use crate::othello::position::Position;
+
+    const CORNERS: u64 = 0x8100000000000081;
+
+    #[test]
+    fn test_pack_column() {
+        for bits in 0..=255u8 {
+            let column = unpack_column(bits) | (bits as u64 & 1) | ((bits as u64 >> 7) << 56);
+            assert_eq!(pack_column(column), bits as usize);
+            assert_eq!(pack_column(column | 0x7e), bits as usize);
+        }
+    }
+
+    #[test]
+    fn test_get_stable_discs() {
+        // Corners are always stable
+        assert_eq!(get_stable_discs(CORNERS, 0), CORNERS);
+
+        // Edge discs next to an own corner are stable
+        assert_eq!(get_stable_discs(0x7, 0x8), 0x7);
+
+        // Initial discs are not stable
+        let position = Position::new();
+        assert_eq!(get_stable_discs(position.player, position.opponent), 0);
+
+        // All discs of a full board are stable
+        let position = Position::new_from_bitboards(0x00ff00ff00ff00ff, 0xff00ff00ff00ff00);
+        assert_eq!(
+            get_stable_discs(position.player, position.opponent),
+            position.player
+        );
+
+        // Stable discs keep their color in all positions that can follow
+        for problem in parse_ffo_problems().iter().filter(|p| p.depth <= 14) {
+            let mut position = problem.position;
+            while position.count_empty() > 7 && position.has_moves() {
+                position.do_move(position.get_moves().trailing_zeros() as usize);
+            }
+
+            let stable = get_stable_discs(position.player, position.opponent);
+
+            // Positions paired with whether the player to move owns the stable discs
+            let mut positions = vec![(position, true)];
+
+            while let Some((position, owns_stable)) = positions.pop() {
+                let owner = if owns_stable {
+                    position.player
+                } else {
+                    position.opponent
+                };
+                assert_eq!(owner & stable, stable);
+
+                let mut passed = position;
+                passed.pass();
+
+                for child in position.children() {
+                    positions.push((child, !owns_stable));
+                }
+                for child in passed.children() {
+                    positions.push((child, owns_stable));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_get_stable_edges() {
+        // Full edge of one color
+        assert_eq!(get_stable_edges(0xff, 0), 0xff);
+
+        // Edge discs that can be flipped are not stable
+        assert_eq!(get_stable_edges(0x02, 0x04), 0);
+
+        // Inner discs are never returned
+        assert_eq!(get_stable_edges(u64::MAX, 0), 0xff818181818181ff);
+    }
+}
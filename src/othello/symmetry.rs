@@ -0,0 +1,208 @@
+// Symmetries of the board, for bitboards and for square indices.
+// Squares are numbered as in `squares`: A1 is 0, H1 is 7 and H8 is 63.
+// Rotations are clockwise, with row 1 at the top as in `print_bitset`.
+
+/// Mirrors a bitboard left to right
+pub fn flip_horizontal(bitboard: u64) -> u64 {
+    const K1: u64 = 0x5555555555555555;
+    const K2: u64 = 0x3333333333333333;
+    const K4: u64 = 0x0f0f0f0f0f0f0f0f;
+
+    let mut x = bitboard;
+    x = ((x >> 1) & K1) | ((x & K1) << 1);
+    x = ((x >> 2) & K2) | ((x & K2) << 2);
+    ((x >> 4) & K4) | ((x & K4) << 4)
+}
+
+/// Mirrors a bitboard top to bottom
+pub fn flip_vertical(bitboard: u64) -> u64 {
+    bitboard.swap_bytes()
+}
+
+/// Mirrors a bitboard along the a1-h8 diagonal
+pub fn flip_diagonal(bitboard: u64) -> u64 {
+    const K1: u64 = 0x5500550055005500;
+    const K2: u64 = 0x3333000033330000;
+    const K4: u64 = 0x0f0f0f0f00000000;
+
+    let mut x = bitboard;
+    let mut t = K4 & (x ^ (x << 28));
+    x ^= t ^ (t >> 28);
+    t = K2 & (x ^ (x << 14));
+    x ^= t ^ (t >> 14);
+    t = K1 & (x ^ (x << 7));
+    x ^ t ^ (t >> 7)
+}
+
+/// Mirrors a bitboard along the a8-h1 diagonal
+pub fn flip_anti_diagonal(bitboard: u64) -> u64 {
+    flip_diagonal(bitboard).reverse_bits()
+}
+
+pub fn rotate_90(bitboard: u64) -> u64 {
+    flip_horizontal(flip_diagonal(bitboard))
+}
+
+pub fn rotate_180(bitboard: u64) -> u64 {
+    bitboard.reverse_bits()
+}
+
+pub fn rotate_270(bitboard: u64) -> u64 {
+    flip_vertical(flip_diagonal(bitboard))
+}
+
+pub fn flip_horizontal_square(square: usize) -> usize {
+    square ^ 7
+}
+
+pub fn flip_vertical_square(square: usize) -> usize {
+    square ^ 56
+}
+
+pub fn flip_diagonal_square(square: usize) -> usize {
+    (square >> 3) | ((square & 7) << 3)
+}
+
+pub fn flip_anti_diagonal_square(square: usize) -> usize {
+    63 - flip_diagonal_square(square)
+}
+
+pub fn rotate_90_square(square: usize) -> usize {
+    flip_horizontal_square(flip_diagonal_square(square))
+}
+
+pub fn rotate_180_square(square: usize) -> usize {
+    63 - square
+}
+
+pub fn rotate_270_square(square: usize) -> usize {
+    flip_vertical_square(flip_diagonal_square(square))
+}
+
+/// One of the eight symmetries of the board, numbered as in Edax: bit 0 mirrors
+/// left to right, bit 1 top to bottom and bit 2 along the a1-h8 diagonal, in that order.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Symmetry(u8);
+
+impl Symmetry {
+    pub const IDENTITY: Self = Self(0);
+
+    /// Returns the symmetry with Edax's number `index`, which is below 8
+    pub fn new(index: usize) -> Self {
+        assert!(index < 8, "Invalid symmetry {}", index);
+        Self(index as u8)
+    }
+
+    /// Returns all symmetries, starting with `IDENTITY`
+    pub fn all() -> impl Iterator<Item = Self> {
+        (0..8).map(Self)
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// Returns the symmetry that undoes this one
+    pub fn inverse(self) -> Self {
+        if self.0 & 4 == 0 {
+            return self;
+        }
+
+        // Mirroring along the diagonal turns left-right mirrors into top-bottom mirrors
+        Self(4 | ((self.0 & 1) << 1) | ((self.0 & 2) >> 1))
+    }
+
+    pub fn apply(self, bitboard: u64) -> u64 {
+        let mut x = bitboard;
+        if self.0 & 1 != 0 {
+            x = flip_horizontal(x);
+        }
+        if self.0 & 2 != 0 {
+            x = flip_vertical(x);
+        }
+        if self.0 & 4 != 0 {
+            x = flip_diagonal(x);
+        }
+        x
+    }
+
+    /// Returns the square that `square` is moved to, which must be on the board
+    pub fn apply_square(self, square: usize) -> usize {
+        debug_assert!(square < 64);
+
+        let mut x = square;
+        if self.0 & 1 != 0 {
+            x = flip_horizontal_square(x);
+        }
+        if self.0 & 2 != 0 {
+            x = flip_vertical_square(x);
+        }
+        if self.0 & 4 != 0 {
+            x = flip_diagonal_square(x);
+        }
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::othello::squares::*;
+
+    fn bit(square: usize) -> u64 {
+        1u64 << square
+    }
+
+    /// Bitboard function, square function and where it moves B1
+    type Case = (fn(u64) -> u64, fn(usize) -> usize, usize);
+
+    #[test]
+    fn test_flips_and_rotations() {
+        let cases: [Case; 7] = [
+            (flip_horizontal, flip_horizontal_square, G1),
+            (flip_vertical, flip_vertical_square, B8),
+            (flip_diagonal, flip_diagonal_square, A2),
+            (flip_anti_diagonal, flip_anti_diagonal_square, H7),
+            (rotate_90, rotate_90_square, H2),
+            (rotate_180, rotate_180_square, G8),
+            (rotate_270, rotate_270_square, A7),
+        ];
+
+        for (flip, flip_square, b1_moved_to) in cases {
+            assert_eq!(flip_square(B1), b1_moved_to);
+
+            for square in 0..64 {
+                assert_eq!(flip(bit(square)), bit(flip_square(square)));
+            }
+        }
+
+        let bitboard = 0x0123456789abcdef;
+        assert_eq!(rotate_90(rotate_270(bitboard)), bitboard);
+        assert_eq!(rotate_90(rotate_90(bitboard)), rotate_180(bitboard));
+    }
+
+    #[test]
+    fn test_symmetry() {
+        assert_eq!(Symmetry::new(1).apply(bit(A1)), bit(H1));
+        assert_eq!(Symmetry::new(2).apply(bit(A1)), bit(A8));
+        assert_eq!(Symmetry::new(4).apply(bit(B1)), bit(A2));
+        assert_eq!(Symmetry::new(7).apply(bit(B1)), bit(H7));
+
+        // All symmetries of an asymmetric bitboard are different
+        let bitboard = bit(B1) | bit(C1);
+        let mut transformed: Vec<_> = Symmetry::all().map(|s| s.apply(bitboard)).collect();
+        transformed.sort();
+        transformed.dedup();
+        assert_eq!(transformed.len(), 8);
+
+        for symmetry in Symmetry::all() {
+            assert_eq!(Symmetry::new(symmetry.index()), symmetry);
+
+            for square in 0..64 {
+                let moved = symmetry.apply_square(square);
+                assert_eq!(symmetry.apply(bit(square)), bit(moved));
+                assert_eq!(symmetry.inverse().apply_square(moved), square);
+            }
+        }
+    }
+}
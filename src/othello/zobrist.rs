@@ -0,0 +1,250 @@
+// Zobrist hashing of positions: every square has a random key for a disc of the
+// player to move and one for a disc of the opponent, and the hash of a position
+// is the XOR of the keys of all its discs.
+//
+// Since the player to move changes with every move, all discs change owner from the
+// point of view of `Position`. `ZobristHash` therefore also keeps the hash of the
+// position as the opponent sees it, which makes moves and passes cheap to apply.
+
+use super::position::Position;
+
+const PLAYER: usize = 0;
+const OPPONENT: usize = 1;
+
+/// Returns the next state and output of the SplitMix64 generator
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    (state, z ^ (z >> 31))
+}
+
+/// Random keys of the discs of the player and opponent on each square
+const SQUARE_KEYS: [[u64; 64]; 2] = {
+    let mut keys = [[0; 64]; 2];
+    let mut state = 0x5357415020484153; // Fixed seed, so hashes are the same in every run
+
+    let mut side = 0;
+    while side < 2 {
+        let mut square = 0;
+        while square < 64 {
+            let (next_state, key) = splitmix64(state);
+            state = next_state;
+            keys[side][square] = key;
+            square += 1;
+        }
+        side += 1;
+    }
+    keys
+};
+
+/// XOR of the keys of the discs in each byte of a bitboard, so a bitboard hashes
+/// with eight lookups instead of one per disc
+static BYTE_KEYS: [[[u64; 256]; 8]; 2] = {
+    let mut keys = [[[0; 256]; 8]; 2];
+
+    let mut side = 0;
+    while side < 2 {
+        let mut byte = 0;
+        while byte < 8 {
+            let mut value = 0;
+            while value < 256 {
+                let mut bit = 0;
+                while bit < 8 {
+                    if value & (1 << bit) != 0 {
+                        keys[side][byte][value] ^= SQUARE_KEYS[side][8 * byte + bit];
+                    }
+                    bit += 1;
+                }
+                value += 1;
+            }
+            byte += 1;
+        }
+        side += 1;
+    }
+    keys
+};
+
+/// Returns the XOR of the keys of `side` for all discs of `bitboard`
+fn hash_bitboard(bitboard: u64, side: usize) -> u64 {
+    let keys = &BYTE_KEYS[side];
+    bitboard
+        .to_le_bytes()
+        .iter()
+        .zip(keys)
+        .fold(0, |hash, (&value, byte_keys)| {
+            hash ^ byte_keys[value as usize]
+        })
+}
+
+/// Returns the Zobrist hash of `position`, this is what `Position::hash` returns
+pub fn hash(position: &Position) -> u64 {
+    hash_bitboard(position.player, PLAYER) ^ hash_bitboard(position.opponent, OPPONENT)
+}
+
+/// Zobrist hash that follows a position through moves and passes.
+///
+/// The searches don't use it: a move changes the hash by the keys of the flipped
+/// discs on both sides, which takes as many lookups as hashing the position again.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ZobristHash {
+    /// Hash of the position
+    hash: u64,
+
+    /// Hash of the position after a pass
+    passed: u64,
+}
+
+impl ZobristHash {
+    pub fn new(position: &Position) -> Self {
+        Self {
+            hash: hash(position),
+            passed: hash_bitboard(position.opponent, PLAYER)
+                ^ hash_bitboard(position.player, OPPONENT),
+        }
+    }
+
+    pub fn value(&self) -> u64 {
+        self.hash
+    }
+
+    /// Updates the hash for `Position::do_move` with the same `index`,
+    /// `flips` is what that returned.
+    pub fn do_move(&mut self, index: usize, flips: u64) {
+        // The flipped discs change from one side to the other in both hashes
+        let flipped = hash_bitboard(flips, PLAYER) ^ hash_bitboard(flips, OPPONENT);
+
+        *self = Self {
+            hash: self.passed ^ flipped ^ SQUARE_KEYS[OPPONENT][index],
+            passed: self.hash ^ flipped ^ SQUARE_KEYS[PLAYER][index],
+        };
+    }
+
+    /// Updates the hash for `Position::undo_move` with the same arguments
+    pub fn undo_move(&mut self, index: usize, flips: u64) {
+        let flipped = hash_bitboard(flips, PLAYER) ^ hash_bitboard(flips, OPPONENT);
+
+        *self = Self {
+            hash: self.passed ^ flipped ^ SQUARE_KEYS[PLAYER][index],
+            passed: self.hash ^ flipped ^ SQUARE_KEYS[OPPONENT][index],
+        };
+    }
+
+    /// Updates the hash for `Position::pass`
+    pub fn pass(&mut self) {
+        std::mem::swap(&mut self.hash, &mut self.passed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    /// Returns the positions of random games, all different
+    fn random_positions(count: usize) -> Vec<Position> {
+        let mut rng = StdRng::seed_from_u64(14);
+        let mut positions = HashSet::new();
+
+        while positions.len() < count {
+            let mut position = Position::new();
+
+            loop {
+                if !position.has_moves() {
+                    position.pass();
+                    if !position.has_moves() {
+                        break;
+                    }
+                }
+
+                let children = position.children();
+                position = *children.choose(&mut rng).unwrap();
+                positions.insert((position.player, position.opponent));
+            }
+        }
+
+        positions
+            .into_iter()
+            .take(count)
+            .map(|(player, opponent)| Position::new_from_bitboards(player, opponent))
+            .collect()
+    }
+
+    #[test]
+    fn test_keys_are_unique() {
+        let mut keys: Vec<_> = SQUARE_KEYS.iter().flatten().collect();
+        keys.sort();
+        keys.dedup();
+        assert_eq!(keys.len(), 128);
+    }
+
+    #[test]
+    fn test_incremental_update() {
+        let mut rng = StdRng::seed_from_u64(14);
+
+        for _ in 0..100 {
+            let mut position = Position::new();
+            let mut hash = ZobristHash::new(&position);
+
+            loop {
+                if !position.has_moves() {
+                    position.pass();
+                    hash.pass();
+                    assert_eq!(hash, ZobristHash::new(&position));
+
+                    if !position.has_moves() {
+                        break;
+                    }
+                }
+
+                let moves = position.children_with_index();
+                let (move_, _) = *moves.choose(&mut rng).unwrap();
+
+                let before = (position, hash);
+                let flips = position.do_move(move_);
+                hash.do_move(move_, flips);
+                assert_eq!(hash, ZobristHash::new(&position));
+                assert_eq!(hash.value(), position.hash());
+
+                let (mut undone, mut undone_hash) = (position, hash);
+                undone.undo_move(move_, flips);
+                undone_hash.undo_move(move_, flips);
+                assert_eq!((undone, undone_hash), before);
+            }
+        }
+    }
+
+    #[test]
+    fn test_collisions() {
+        let positions = random_positions(100_000);
+
+        // No full 64-bit collisions
+        let hashes: HashSet<_> = positions.iter().map(|p| p.hash()).collect();
+        assert_eq!(hashes.len(), positions.len());
+
+        // Both the low and the high bits spread positions over a table as random indexes
+        // would, which fill 1 - 1/e of the table with as many positions as entries
+        let bits = 16;
+        let mask = (1u64 << bits) - 1;
+        let sample = &positions[..1 << bits];
+
+        let low_bits = |hash: u64| hash & mask;
+        let high_bits = |hash: u64| hash >> (64 - bits);
+
+        for index in [&low_bits as &dyn Fn(u64) -> u64, &high_bits] {
+            let indexes: HashSet<_> = sample.iter().map(|p| index(p.hash())).collect();
+            let filled = indexes.len() as f64 / sample.len() as f64;
+            assert!(
+                (filled - (1.0 - (-1.0f64).exp())).abs() < 0.01,
+                "{}",
+                filled
+            );
+        }
+    }
+}